@@ -0,0 +1,90 @@
+use std::{
+    env,
+    io::{BufReader, Read},
+    time::Instant,
+};
+
+use dec01::{Mode, Scanner};
+
+const FRAGMENTS: [&[u8]; 12] = [
+    b"one", b"two", b"eight", b"nine", b"eigh", b"thre", b"sev", b"x", b"qz", b"abc", b"oneight",
+    b"twone",
+];
+
+/// Calibration document of at least `bytes` bytes, generated on the fly so
+/// the benchmark never holds more than a line of it in memory.
+struct Synthetic {
+    remaining: u64,
+    state: u64,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl Synthetic {
+    fn new(bytes: u64) -> Self {
+        Self {
+            remaining: bytes,
+            state: 0x2545_f491_4f6c_dd1d,
+            line: vec![],
+            pos: 0,
+        }
+    }
+
+    fn next_rand(&mut self) -> usize {
+        self.state = self
+            .state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.state >> 33) as usize
+    }
+
+    fn next_line(&mut self) {
+        self.line.clear();
+        self.pos = 0;
+        for idx in 0..12 {
+            if idx == 8 {
+                let digit = b'1' + (self.next_rand() % 9) as u8;
+                self.line.push(digit);
+            }
+            let fragment = FRAGMENTS[self.next_rand() % FRAGMENTS.len()];
+            self.line.extend_from_slice(fragment);
+        }
+        self.line.push(b'\n');
+    }
+}
+
+impl Read for Synthetic {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.line.len() {
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+            self.next_line();
+        }
+        let len = buf.len().min(self.line.len() - self.pos);
+        buf[..len].copy_from_slice(&self.line[self.pos..self.pos + len]);
+        self.pos += len;
+        self.remaining = self.remaining.saturating_sub(len as u64);
+        Ok(len)
+    }
+}
+
+fn main() {
+    // Set DEC01_BENCH_BYTES to e.g. 4294967296 for a multi-gigabyte run
+    let bytes: u64 = env::var("DEC01_BENCH_BYTES")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(256 << 20);
+
+    for mode in [Mode::Digits, Mode::DigitsAndWords] {
+        let scanner = Scanner::new(&mode.vocabulary());
+        let reader = BufReader::with_capacity(1 << 16, Synthetic::new(bytes));
+
+        let start = Instant::now();
+        let sum = scanner.calibration_sum(reader).unwrap();
+        let elapsed = start.elapsed();
+
+        let mib_per_sec = bytes as f64 / (1 << 20) as f64 / elapsed.as_secs_f64();
+        println!("{mode:?}: {bytes} bytes in {elapsed:.2?} ({mib_per_sec:.0} MiB/s), sum {sum}");
+    }
+}
@@ -1,22 +1,19 @@
 use std::{
+    env,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufReader},
 };
 
+use dec01::{calibration_report, calibration_sum, Mode};
+
 fn main() {
     let reader = BufReader::new(File::open("input.txt").unwrap());
 
-    let sum = reader
-        .lines()
-        .map(Result::unwrap)
-        .map(|line| {
-            let mut digits = line.chars().filter(|c| c.is_numeric());
-
-            let first = digits.next().unwrap();
-            let last = digits.last().unwrap_or(first);
-            format!("{first}{last}").parse::<i32>().unwrap()
-        })
-        .sum::<i32>();
+    let sum = if env::args().any(|arg| arg == "--report") {
+        calibration_report(reader, Mode::Digits, io::stdout().lock()).unwrap()
+    } else {
+        calibration_sum(reader, Mode::Digits).unwrap()
+    };
 
     println!("{sum}");
 }
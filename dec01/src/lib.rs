@@ -0,0 +1,588 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead, Write},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use aho_corasick::{automaton::Automaton, dfa::DFA, AhoCorasick, Anchored, MatchKind, PatternID};
+
+const WORDS: [&str; 9] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Digits,
+    DigitsAndWords,
+}
+
+impl Mode {
+    pub fn vocabulary(self) -> Vocabulary {
+        match self {
+            Mode::Digits => Vocabulary::digits(),
+            Mode::DigitsAndWords => Vocabulary::english(),
+        }
+    }
+
+    /// A scanner for this mode's vocabulary, built the first time it is asked
+    /// for and shared after that.
+    pub fn scanner(self) -> &'static Scanner {
+        static DIGITS: OnceLock<Scanner> = OnceLock::new();
+        static DIGITS_AND_WORDS: OnceLock<Scanner> = OnceLock::new();
+
+        let scanner = match self {
+            Mode::Digits => &DIGITS,
+            Mode::DigitsAndWords => &DIGITS_AND_WORDS,
+        };
+        scanner.get_or_init(|| Scanner::new(&self.vocabulary()))
+    }
+}
+
+/// Tokens that count as a digit when scanning a calibration line, each
+/// mapped to the value it stands for. Where several tokens match at the same
+/// position the longest one wins, so `iv` beats `i` in a roman vocabulary.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vocabulary {
+    tokens: Vec<(String, u32)>,
+}
+
+impl Vocabulary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn digits() -> Self {
+        (0..10).fold(Self::new(), |vocab, digit| {
+            vocab.with(&digit.to_string(), digit)
+        })
+    }
+
+    /// The digits `1` to `9` and their English names. `0` has no name, so it
+    /// is left out of this vocabulary altogether.
+    pub fn english() -> Self {
+        WORDS
+            .iter()
+            .zip(1..)
+            .fold(Self::new(), |vocab, (word, value)| {
+                vocab.with(&value.to_string(), value).with(word, value)
+            })
+    }
+
+    pub fn with(mut self, token: &str, value: u32) -> Self {
+        self.insert(token, value);
+        self
+    }
+
+    pub fn insert(&mut self, token: &str, value: u32) {
+        assert!(!token.is_empty(), "Empty calibration token");
+        self.tokens.retain(|(existing, _)| existing != token);
+        let idx = self
+            .tokens
+            .partition_point(|(existing, _)| existing.len() >= token.len());
+        self.tokens.insert(idx, (token.to_string(), value));
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.tokens
+            .iter()
+            .map(|(token, value)| (token.as_str(), *value))
+    }
+
+    /// Builds a [`Scanner`] for just this line. Keep one around instead when
+    /// calibrating more than a few lines.
+    pub fn calibration_value(&self, line: &str) -> Option<u64> {
+        Scanner::new(self).calibration_value(line.as_bytes())
+    }
+
+    pub fn calibration_sum<R: BufRead>(&self, reader: R) -> Result<u64, CalibrationError> {
+        Scanner::new(self).calibration_sum(reader)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseVocabularyError {
+    pub line: usize,
+}
+
+/// Parses one `token value` pair per line. Blank lines and lines starting
+/// with `#` are ignored.
+impl FromStr for Vocabulary {
+    type Err = ParseVocabularyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut vocab = Self::new();
+        for (line_num, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = ParseVocabularyError { line: line_num + 1 };
+            let mut fields = line.split_whitespace();
+            let token = fields.next().ok_or(err)?;
+            let value = fields.next().ok_or(err)?.parse().map_err(|_| err)?;
+            if fields.next().is_some() {
+                return Err(err);
+            }
+            vocab.insert(token, value);
+        }
+        Ok(vocab)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenMatch<'a> {
+    pub token: &'a str,
+    pub offset: usize,
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration<'a> {
+    pub line: usize,
+    pub first: TokenMatch<'a>,
+    pub last: TokenMatch<'a>,
+}
+
+impl Calibration<'_> {
+    /// Widened to `u64`, so any pair of `u32` token values fits.
+    pub fn value(&self) -> u64 {
+        u64::from(self.first.value) * 10 + u64::from(self.last.value)
+    }
+}
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    NoDigits { line: usize },
+    Unreadable { line: usize, source: io::Error },
+    Overflow { line: usize },
+}
+
+impl Display for CalibrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDigits { line } => write!(f, "line {line}: no digits"),
+            Self::Unreadable { line, source } => write!(f, "line {line}: {source}"),
+            Self::Overflow { line } => write!(f, "line {line}: sum overflows"),
+        }
+    }
+}
+
+impl Error for CalibrationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NoDigits { .. } | Self::Overflow { .. } => None,
+            Self::Unreadable { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Finds calibration tokens with two automata: a leftmost-longest one for the
+/// first token, and one over the reversed tokens that is stepped backwards
+/// from the end of the line for the last. Lines are read into one reused
+/// buffer, so memory use does not grow with the size of the input.
+pub struct Scanner {
+    tokens: Vec<(String, u32)>,
+    forward: AhoCorasick,
+    backward: DFA,
+}
+
+impl Scanner {
+    pub fn new(vocab: &Vocabulary) -> Self {
+        let tokens = vocab
+            .tokens()
+            .map(|(token, value)| (token.to_string(), value))
+            .collect();
+        let forward = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(vocab.tokens().map(|(token, _)| token))
+            .unwrap();
+        let backward = DFA::builder()
+            .match_kind(MatchKind::Standard)
+            .build(
+                vocab
+                    .tokens()
+                    .map(|(token, _)| token.bytes().rev().collect::<Vec<_>>()),
+            )
+            .unwrap();
+
+        Self {
+            tokens,
+            forward,
+            backward,
+        }
+    }
+
+    fn token_match(&self, pattern: PatternID, offset: usize) -> TokenMatch<'_> {
+        let (token, value) = &self.tokens[pattern];
+        TokenMatch {
+            token,
+            offset,
+            value: *value,
+        }
+    }
+
+    fn first(&self, line: &[u8]) -> Option<TokenMatch<'_>> {
+        self.forward
+            .find(line)
+            .map(|found| self.token_match(found.pattern(), found.start()))
+    }
+
+    fn last(&self, line: &[u8]) -> Option<TokenMatch<'_>> {
+        let mut state = self.backward.start_state(Anchored::No).unwrap();
+        for (offset, &byte) in line.iter().enumerate().rev() {
+            state = self.backward.next_state(Anchored::No, state, byte);
+            if self.backward.is_match(state) {
+                // Every token matching here starts at this byte; keep the longest
+                let pattern = (0..self.backward.match_len(state))
+                    .map(|idx| self.backward.match_pattern(state, idx))
+                    .max_by_key(|&pattern| self.backward.pattern_len(pattern))
+                    .unwrap();
+                return Some(self.token_match(pattern, offset));
+            }
+        }
+        None
+    }
+
+    pub fn calibrate(
+        &self,
+        line: &[u8],
+        line_num: usize,
+    ) -> Result<Calibration<'_>, CalibrationError> {
+        let first = self
+            .first(line)
+            .ok_or(CalibrationError::NoDigits { line: line_num })?;
+        let last = self.last(line).unwrap_or(first);
+        Ok(Calibration {
+            line: line_num,
+            first,
+            last,
+        })
+    }
+
+    pub fn calibration_value(&self, line: &[u8]) -> Option<u64> {
+        self.calibrate(line, 0)
+            .ok()
+            .map(|calibration| calibration.value())
+    }
+
+    /// Calibrates each line of `reader`, numbering lines from 1. Iteration
+    /// stops after the first line that cannot be read.
+    pub fn calibrations<R: BufRead>(&self, reader: R) -> Calibrations<'_, R> {
+        Calibrations {
+            scanner: self,
+            reader,
+            buf: vec![],
+            line: 0,
+            done: false,
+        }
+    }
+
+    pub fn calibration_sum<R: BufRead>(&self, reader: R) -> Result<u64, CalibrationError> {
+        self.calibrations(reader)
+            .try_fold(0u64, |sum, calibration| {
+                let calibration = calibration?;
+                sum.checked_add(calibration.value())
+                    .ok_or(CalibrationError::Overflow {
+                        line: calibration.line,
+                    })
+            })
+    }
+
+    /// Writes one line to `out` for every input line, giving either the tokens
+    /// picked for it or the reason it was rejected. Returns the sum of the
+    /// accepted lines, or an [`io::ErrorKind::InvalidData`] error if that
+    /// sum overflows.
+    pub fn report<R: BufRead, W: Write>(&self, reader: R, mut out: W) -> io::Result<u64> {
+        let mut sum = 0u64;
+        let mut rejected = 0;
+        for calibration in self.calibrations(reader) {
+            match calibration {
+                Ok(calibration) => {
+                    let Calibration { line, first, last } = calibration;
+                    writeln!(
+                        out,
+                        "line {line}: {} (first `{}` at {}, last `{}` at {})",
+                        calibration.value(),
+                        first.token,
+                        first.offset,
+                        last.token,
+                        last.offset
+                    )?;
+                    sum = sum.checked_add(calibration.value()).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            CalibrationError::Overflow { line },
+                        )
+                    })?;
+                }
+                Err(err) => {
+                    writeln!(out, "rejected {err}")?;
+                    rejected += 1;
+                }
+            }
+        }
+        writeln!(out, "{rejected} rejected")?;
+        Ok(sum)
+    }
+}
+
+pub struct Calibrations<'a, R> {
+    scanner: &'a Scanner,
+    reader: R,
+    buf: Vec<u8>,
+    line: usize,
+    done: bool,
+}
+
+impl<'a, R: BufRead> Iterator for Calibrations<'a, R> {
+    type Item = Result<Calibration<'a>, CalibrationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        self.buf.clear();
+        self.line += 1;
+        match self.reader.read_until(b'\n', &mut self.buf) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => {
+                let line = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                Some(self.scanner.calibrate(line, self.line))
+            }
+            Err(source) => {
+                self.done = true;
+                Some(Err(CalibrationError::Unreadable {
+                    line: self.line,
+                    source,
+                }))
+            }
+        }
+    }
+}
+
+pub fn calibration_value(line: &str, mode: Mode) -> Option<u64> {
+    mode.scanner().calibration_value(line.as_bytes())
+}
+
+pub fn calibration_sum<R: BufRead>(reader: R, mode: Mode) -> Result<u64, CalibrationError> {
+    mode.scanner().calibration_sum(reader)
+}
+
+pub fn calibration_report<R: BufRead, W: Write>(reader: R, mode: Mode, out: W) -> io::Result<u64> {
+    mode.scanner().report(reader, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        calibration_report, calibration_sum, calibration_value, CalibrationError, Mode,
+        ParseVocabularyError, Scanner, TokenMatch, Vocabulary,
+    };
+
+    #[test]
+    fn test_calibration_value() {
+        assert_eq!(calibration_value("pqr3stu8vwx", Mode::Digits), Some(38));
+        assert_eq!(calibration_value("treb7uchet", Mode::Digits), Some(77));
+        assert_eq!(calibration_value("two1nine", Mode::Digits), Some(11));
+        assert_eq!(calibration_value("nodigits", Mode::Digits), None);
+
+        assert_eq!(
+            calibration_value("two1nine", Mode::DigitsAndWords),
+            Some(29)
+        );
+        assert_eq!(
+            calibration_value("zoneight234", Mode::DigitsAndWords),
+            Some(14)
+        );
+
+        assert_eq!(calibration_value("a0b5", Mode::Digits), Some(5));
+        assert_eq!(calibration_value("a0b5", Mode::DigitsAndWords), Some(55));
+    }
+
+    #[test]
+    fn test_overlapping_words() {
+        assert_eq!(calibration_value("eightwo", Mode::DigitsAndWords), Some(82));
+        assert_eq!(calibration_value("oneight", Mode::DigitsAndWords), Some(18));
+        assert_eq!(
+            calibration_value("xtwone3four", Mode::DigitsAndWords),
+            Some(24)
+        );
+        assert_eq!(calibration_value("eightwo", Mode::Digits), None);
+    }
+
+    #[test]
+    fn test_part1() {
+        let input = include_str!("../input_simple_1.txt");
+        assert_eq!(
+            calibration_sum(input.as_bytes(), Mode::Digits).unwrap(),
+            142
+        );
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = include_str!("../input_simple_2.txt");
+        assert_eq!(
+            calibration_sum(input.as_bytes(), Mode::DigitsAndWords).unwrap(),
+            281
+        );
+    }
+
+    #[test]
+    fn test_custom_vocabulary() {
+        let vocab = Vocabulary::digits()
+            .with("zero", 0)
+            .with("ten", 10)
+            .with("i", 1)
+            .with("iv", 4)
+            .with("v", 5);
+
+        assert_eq!(vocab.calibration_value("zero3ten"), Some(10));
+        assert_eq!(vocab.calibration_value("xxivxx"), Some(45));
+        assert_eq!(vocab.calibration_value("vi"), Some(51));
+        assert_eq!(vocab.calibration_value("three"), None);
+    }
+
+    #[test]
+    fn test_vocabulary_from_str() {
+        let vocab: Vocabulary = "# German\neins 1\nzwei 2\n\ndrei 3\n".parse().unwrap();
+        assert_eq!(vocab.calibration_value("xzweinsx"), Some(21));
+        assert_eq!(vocab.calibration_value("drei7"), Some(33));
+
+        assert_eq!(
+            "eins 1\nzwei\n".parse::<Vocabulary>(),
+            Err(ParseVocabularyError { line: 2 })
+        );
+        assert_eq!(
+            "eins one\n".parse::<Vocabulary>(),
+            Err(ParseVocabularyError { line: 1 })
+        );
+    }
+
+    #[test]
+    fn test_large_values() {
+        let vocab: Vocabulary = "big 4294967295\n".parse().unwrap();
+        let value = u64::from(u32::MAX) * 11;
+        assert_eq!(vocab.calibration_value("xbigx"), Some(value));
+
+        let scanner = Scanner::new(&vocab);
+        assert_eq!(scanner.calibration_value(b"xbigx"), Some(value));
+        assert_eq!(
+            scanner.calibration_sum("big\nbig\n".as_bytes()).unwrap(),
+            2 * value
+        );
+    }
+
+    #[test]
+    fn test_scanner_tokens() {
+        let vocab = Vocabulary::english()
+            .with("zero", 0)
+            .with("i", 1)
+            .with("iv", 4)
+            .with("v", 5)
+            .with("vi", 6);
+        let scanner = Scanner::new(&vocab);
+
+        let lines = include_str!("../input_simple_2.txt").lines().chain([
+            "eightwo",
+            "oneight",
+            "xxivxx",
+            "vivi",
+            "zerone",
+            "nothing here",
+            "",
+        ]);
+        // The roman `i` inside `nine`, `eight` and `sixteen` starts after the
+        // word does, so it is the last token on those lines
+        let expected = [
+            Some(21),
+            Some(83),
+            Some(13),
+            Some(24),
+            Some(42),
+            Some(14),
+            Some(71),
+            Some(82),
+            Some(11),
+            Some(45),
+            Some(61),
+            Some(1),
+            Some(11),
+            None,
+        ];
+        for (line, value) in lines.zip(expected) {
+            assert_eq!(scanner.calibration_value(line.as_bytes()), value, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_scanner_line_endings() {
+        let scanner = Scanner::new(&Mode::DigitsAndWords.vocabulary());
+        let input = "two1nine\r\neightwothree\r\nabcone2threexyz";
+        assert_eq!(
+            scanner.calibration_sum(input.as_bytes()).unwrap(),
+            29 + 83 + 13
+        );
+    }
+
+    #[test]
+    fn test_calibrations() {
+        let scanner = Scanner::new(&Mode::DigitsAndWords.vocabulary());
+        let input = "xtwone3four\nnothing\n7\n";
+        let mut calibrations = scanner.calibrations(input.as_bytes());
+
+        let calibration = calibrations.next().unwrap().unwrap();
+        assert_eq!(calibration.line, 1);
+        assert_eq!(
+            calibration.first,
+            TokenMatch {
+                token: "two",
+                offset: 1,
+                value: 2
+            }
+        );
+        assert_eq!(
+            calibration.last,
+            TokenMatch {
+                token: "four",
+                offset: 7,
+                value: 4
+            }
+        );
+        assert_eq!(calibration.value(), 24);
+
+        assert!(matches!(
+            calibrations.next(),
+            Some(Err(CalibrationError::NoDigits { line: 2 }))
+        ));
+        assert_eq!(calibrations.next().unwrap().unwrap().value(), 77);
+        assert!(calibrations.next().is_none());
+
+        assert!(matches!(
+            scanner.calibration_sum(input.as_bytes()),
+            Err(CalibrationError::NoDigits { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_report() {
+        let input = "two1nine\nnothing\neightwo\n";
+        let mut out = vec![];
+        let sum = calibration_report(input.as_bytes(), Mode::DigitsAndWords, &mut out).unwrap();
+
+        assert_eq!(sum, 29 + 82);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "line 1: 29 (first `two` at 0, last `nine` at 4)\n\
+             rejected line 2: no digits\n\
+             line 3: 82 (first `eight` at 0, last `two` at 4)\n\
+             1 rejected\n"
+        );
+    }
+}
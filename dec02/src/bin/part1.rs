@@ -1,6 +1,17 @@
-use dec02::part1;
+use std::{env, fs};
+
+use dec02::{default_bag, part1_with_bag, Round};
 
 fn main() {
     let input = include_str!("../../input.txt");
-    part1(input);
+
+    // Either `--bag "20 red, 5 blue"` or `--bag-file bag.txt`
+    let mut args = env::args().skip(1);
+    let bag: Round = match (args.next().as_deref(), args.next()) {
+        (Some("--bag"), Some(spec)) => spec.parse().unwrap(),
+        (Some("--bag-file"), Some(path)) => fs::read_to_string(path).unwrap().parse().unwrap(),
+        _ => default_bag(),
+    };
+
+    part1_with_bag(input, &bag);
 }
@@ -1,120 +1,479 @@
-const MAX: &Round = &Round {
-    red: 12,
-    green: 13,
-    blue: 14,
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
 };
 
-#[derive(Debug)]
+pub fn default_bag() -> Round {
+    Round::from_counts([("red", 12), ("green", 13), ("blue", 14)])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Game {
     id: i32,
     rounds: Vec<Round>,
 }
 
-impl Game {
-    fn new(input: &str) -> Self {
-        let mut record = input.split(':');
-
-        let id: i32 = record
-            .next()
-            .unwrap()
-            .split(' ')
-            .last()
-            .unwrap()
-            .parse()
-            .unwrap();
-
-        let rounds: Vec<Round> = record
-            .next()
-            .unwrap()
+/// Parses `Game 1: 3 blue, 4 red; 1 red, 2 green`. A game with no rounds is
+/// written `Game 1:`, with nothing after the colon, which tells it apart from
+/// `Game 1: ` with a single empty round.
+impl FromStr for Game {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (label, rounds) = s
+            .split_once(':')
+            .ok_or_else(|| ParseError::MissingRounds(s.to_string()))?;
+
+        let id: i32 = label
+            .strip_prefix("Game ")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| ParseError::BadId(label.to_string()))?;
+
+        if rounds.is_empty() {
+            return Ok(Self { id, rounds: vec![] });
+        }
+        let rounds = rounds
             .split(';')
             .map(str::trim)
-            .map(Round::new)
-            .collect();
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
 
+        Ok(Self { id, rounds })
+    }
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Game {}:", self.id)?;
+        for (idx, round) in self.rounds.iter().enumerate() {
+            let separator = if idx > 0 { ";" } else { "" };
+            write!(f, "{separator} {round}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Game {
+    pub fn new(id: i32, rounds: Vec<Round>) -> Self {
         Self { id, rounds }
     }
 
-    fn is_possible(&self, max: &Round) -> bool {
-        self.rounds
-            .iter()
-            .filter(|&round| round.is_possible(max))
-            .collect::<Vec<_>>()
-            .len()
-            == self.rounds.len()
+    pub fn id(&self) -> i32 {
+        self.id
     }
 
-    fn get_power(&self) -> i32 {
-        let mut red = self.rounds[0].red;
-        let mut green = self.rounds[0].green;
-        let mut blue = self.rounds[0].blue;
+    pub fn rounds(&self) -> &[Round] {
+        &self.rounds
+    }
 
+    fn is_possible(&self, max: &Round) -> bool {
+        self.rounds.iter().all(|round| round.is_possible(max))
+    }
+
+    fn minimum_bag(&self) -> BTreeMap<&str, i32> {
+        let mut bag = BTreeMap::new();
         for round in &self.rounds {
-            if round.red > red {
-                red = round.red;
-            }
-            if round.green > green {
-                green = round.green;
-            }
-            if round.blue > blue {
-                blue = round.blue;
+            for (color, _) in &round.cubes {
+                let count = bag.entry(color.as_str()).or_insert(0);
+                *count = round.count(color).max(*count);
             }
         }
-        red * green * blue
+        bag
+    }
+
+    fn get_power(&self) -> i32 {
+        self.minimum_bag().values().product()
     }
+
+    fn missing_cubes(&self, bag: &Round) -> Vec<(String, i32)> {
+        self.minimum_bag()
+            .into_iter()
+            .filter(|&(color, count)| count > bag.count(color))
+            .map(|(color, count)| (color.to_string(), count - bag.count(color)))
+            .collect()
+    }
+}
+
+/// The fewest cubes of one colour a bag needs to allow every game, and the
+/// first game that needs that many.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorMinimum {
+    pub color: String,
+    pub count: i32,
+    pub game_id: i32,
+}
+
+/// The cubes that would have to be added to a bag for a rejected game to
+/// become possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagChange {
+    pub game_id: i32,
+    pub additions: Vec<(String, i32)>,
+}
+
+fn is_color(color: &str) -> bool {
+    !color.is_empty()
+        && !color
+            .chars()
+            .any(|ch| ch.is_whitespace() || [',', ';', ':'].contains(&ch))
 }
 
-#[derive(Debug)]
+/// The cubes shown in one round, in the order they were listed. A colour that
+/// is not listed counts as zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Round {
-    red: i32,
-    green: i32,
-    blue: i32,
+    cubes: Vec<(String, i32)>,
 }
 
-impl Round {
-    fn new(input: &str) -> Self {
-        let (mut red, mut green, mut blue) = (0, 0, 0);
-        input
-            .split(',')
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    BadId(String),
+    MissingRounds(String),
+    BadCount(String),
+    MissingColor(String),
+    ExtraWords(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadId(label) => write!(f, "bad game id in `{label}`"),
+            Self::MissingRounds(line) => write!(f, "no rounds in `{line}`"),
+            Self::BadCount(entry) => write!(f, "bad cube count in `{entry}`"),
+            Self::MissingColor(entry) => write!(f, "no colour in `{entry}`"),
+            Self::ExtraWords(entry) => write!(f, "more than a count and a colour in `{entry}`"),
+        }
+    }
+}
+
+/// Parses `3 blue, 4 red`. Entries may also be separated by newlines, so a bag
+/// can be kept in a file with one colour per line.
+impl FromStr for Round {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cubes = s
+            .split([',', '\n'])
             .map(str::trim)
-            .map(|count| {
-                let mut record = count.split(' ');
-                let count: i32 = record.next().unwrap().parse().unwrap();
-                let color = record.next().unwrap();
-                (color, count)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let mut record = entry.split_whitespace();
+                let count: i32 = record
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .ok_or_else(|| ParseError::BadCount(entry.to_string()))?;
+                let color = record
+                    .next()
+                    .ok_or_else(|| ParseError::MissingColor(entry.to_string()))?
+                    .to_string();
+                if record.next().is_some() {
+                    return Err(ParseError::ExtraWords(entry.to_string()));
+                }
+                Ok((color, count))
             })
-            .for_each(|cubes| match cubes {
-                ("red", count) => red += count,
-                ("green", count) => green += count,
-                ("blue", count) => blue += count,
-                _ => unimplemented!(),
-            });
+            .collect::<Result<_, _>>()?;
 
-        Self { red, green, blue }
+        Ok(Self { cubes })
+    }
+}
+
+impl Display for Round {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (idx, (color, count)) in self.cubes.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{count} {color}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Round {
+    /// Panics on a colour that could not be written back out and parsed
+    /// again: an empty one, or one containing whitespace, `,`, `;` or `:`.
+    pub fn from_counts<'a>(counts: impl IntoIterator<Item = (&'a str, i32)>) -> Self {
+        let cubes = counts
+            .into_iter()
+            .map(|(color, count)| {
+                assert!(is_color(color), "Bad cube colour {color:?}");
+                (color.to_string(), count)
+            })
+            .collect();
+        Self { cubes }
+    }
+
+    pub fn cubes(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.cubes
+            .iter()
+            .map(|(color, count)| (color.as_str(), *count))
+    }
+
+    pub fn count(&self, color: &str) -> i32 {
+        self.cubes
+            .iter()
+            .filter(|(cube_color, _)| cube_color == color)
+            .map(|(_, count)| count)
+            .sum()
     }
 
     fn is_possible(&self, max: &Round) -> bool {
-        self.red <= max.red && self.green <= max.green && self.blue <= max.blue
+        self.cubes
+            .iter()
+            .all(|(color, _)| self.count(color) <= max.count(color))
     }
 }
 
-pub fn part1(input: &str) {
-    let games = get_games(input);
-    let sum: i32 = games
+pub fn possible_games(input: &str, bag: &Round) -> Vec<i32> {
+    get_games(input)
         .iter()
-        .filter(|game| game.is_possible(MAX))
+        .filter(|game| game.is_possible(bag))
         .map(|game| game.id)
-        .sum();
+        .collect()
+}
+
+pub fn smallest_bag(input: &str) -> Vec<ColorMinimum> {
+    let games = get_games(input);
+    let mut minimums: BTreeMap<&str, ColorMinimum> = BTreeMap::new();
+
+    for game in &games {
+        for (color, count) in game.minimum_bag() {
+            let minimum = minimums.entry(color).or_insert_with(|| ColorMinimum {
+                color: color.to_string(),
+                count,
+                game_id: game.id,
+            });
+            if count > minimum.count {
+                minimum.count = count;
+                minimum.game_id = game.id;
+            }
+        }
+    }
+
+    minimums.into_values().collect()
+}
 
-    println!("{sum}")
+pub fn bag_changes(input: &str, bag: &Round) -> Vec<BagChange> {
+    get_games(input)
+        .iter()
+        .filter(|game| !game.is_possible(bag))
+        .map(|game| BagChange {
+            game_id: game.id,
+            additions: game.missing_cubes(bag),
+        })
+        .collect()
+}
+
+pub fn part1(input: &str) -> i32 {
+    part1_with_bag(input, &default_bag())
 }
 
-pub fn part2(input: &str) {
+pub fn part1_with_bag(input: &str, bag: &Round) -> i32 {
+    let sum = possible_games(input, bag).iter().sum();
+
+    println!("{sum}");
+    sum
+}
+
+pub fn part2(input: &str) -> i32 {
     let games = get_games(input);
 
     let power_sum: i32 = games.iter().map(Game::get_power).sum();
-    println!("{power_sum}")
+    println!("{power_sum}");
+    power_sum
 }
 
 fn get_games(input: &str) -> Vec<Game> {
-    input.lines().map(Game::new).collect()
+    input.lines().map(|line| line.parse().unwrap()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bag_changes, default_bag, get_games, part1, part1_with_bag, part2, possible_games,
+        smallest_bag, BagChange, ColorMinimum, Game, ParseError, Round,
+    };
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_part1() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part1(input), 8);
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part2(input), 2286);
+    }
+
+    #[test]
+    fn test_other_colors() {
+        let games = get_games("Game 7: 2 cyan, 1 red; 3 magenta, 2 red, 1 cyan; 5 magenta");
+        let game = &games[0];
+
+        assert_eq!(game.get_power(), 2 * 2 * 5);
+        assert!(game.is_possible(&Round::from_counts([
+            ("cyan", 2),
+            ("magenta", 5),
+            ("red", 2)
+        ])));
+        assert!(!game.is_possible(&Round::from_counts([("cyan", 2), ("red", 2)])));
+    }
+
+    #[test]
+    fn test_custom_bag() {
+        let input = include_str!("../input_simple.txt");
+        let bag: Round = "20 red, 13 green, 6 blue".parse().unwrap();
+
+        assert_eq!(possible_games(input, &bag), vec![1, 2, 3, 5]);
+        assert_eq!(part1_with_bag(input, &bag), 11);
+
+        let bag: Round = "20 red\n5 blue\n".parse().unwrap();
+        assert_eq!(possible_games(input, &bag), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_bad_bag() {
+        assert_eq!(
+            "12 red, lots blue".parse::<Round>().unwrap_err(),
+            ParseError::BadCount("lots blue".to_string())
+        );
+        assert_eq!(
+            "12".parse::<Round>().unwrap_err(),
+            ParseError::MissingColor("12".to_string())
+        );
+    }
+
+    #[test]
+    fn test_smallest_bag() {
+        let input = include_str!("../input_simple.txt");
+        let minimum = |color: &str, count, game_id| ColorMinimum {
+            color: color.to_string(),
+            count,
+            game_id,
+        };
+
+        assert_eq!(
+            smallest_bag(input),
+            vec![
+                minimum("blue", 15, 4),
+                minimum("green", 13, 3),
+                minimum("red", 20, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bag_changes() {
+        let input = include_str!("../input_simple.txt");
+
+        assert_eq!(
+            bag_changes(input, &default_bag()),
+            vec![
+                BagChange {
+                    game_id: 3,
+                    additions: vec![("red".to_string(), 8)]
+                },
+                BagChange {
+                    game_id: 4,
+                    additions: vec![("blue".to_string(), 1), ("red".to_string(), 2)]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_game_round_trip() {
+        let input = include_str!("../input_simple.txt");
+        for line in input.lines() {
+            assert_eq!(line.parse::<Game>().unwrap().to_string(), line);
+        }
+
+        let game = Game::new(
+            9,
+            vec![
+                Round::from_counts([("blue", 3), ("red", 4)]),
+                Round::from_counts([("green", 2)]),
+            ],
+        );
+        assert_eq!(game.to_string(), "Game 9: 3 blue, 4 red; 2 green");
+    }
+
+    #[test]
+    fn test_bad_game() {
+        assert_eq!(
+            "Game 1 3 blue".parse::<Game>().unwrap_err(),
+            ParseError::MissingRounds("Game 1 3 blue".to_string())
+        );
+        assert_eq!(
+            "Round 1: 3 blue".parse::<Game>().unwrap_err(),
+            ParseError::BadId("Round 1".to_string())
+        );
+        assert_eq!(
+            "Game 1: 3 blue; red".parse::<Game>().unwrap_err(),
+            ParseError::BadCount("red".to_string())
+        );
+        assert_eq!(
+            "Game 1: 3 light blue".parse::<Game>().unwrap_err(),
+            ParseError::ExtraWords("3 light blue".to_string())
+        );
+        assert_eq!(
+            "3 blue green".parse::<Round>().unwrap_err(),
+            ParseError::ExtraWords("3 blue green".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Bad cube colour")]
+    fn test_color_with_space() {
+        Round::from_counts([("light blue", 3)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bad cube colour")]
+    fn test_color_with_separator() {
+        Round::from_counts([("red;", 3)]);
+    }
+
+    #[test]
+    fn test_game_without_rounds() {
+        let game = Game::new(1, vec![]);
+        assert_eq!(game.to_string(), "Game 1:");
+        assert_eq!("Game 1:".parse::<Game>().unwrap(), game);
+
+        let game = Game::new(1, vec![Round::from_counts([])]);
+        assert_eq!(game.to_string(), "Game 1: ");
+        assert_eq!("Game 1: ".parse::<Game>().unwrap(), game);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_game_json() {
+        let game: Game = "Game 2: 1 blue, 2 green; 3 green".parse().unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+        assert_eq!(serde_json::from_str::<Game>(&json).unwrap(), game);
+    }
+
+    fn arb_round() -> impl Strategy<Value = Round> {
+        // Any colour that survives `from_counts`, with any count
+        prop::collection::vec(("[^\\s,;:]{1,8}", any::<i32>()), 0..5).prop_map(|cubes| {
+            Round::from_counts(cubes.iter().map(|(color, count)| (color.as_str(), *count)))
+        })
+    }
+
+    fn arb_game() -> impl Strategy<Value = Game> {
+        (0..1000, prop::collection::vec(arb_round(), 0..6))
+            .prop_map(|(id, rounds)| Game::new(id, rounds))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_game_round_trip(game in arb_game()) {
+            prop_assert_eq!(game.to_string().parse::<Game>().unwrap(), game);
+        }
+    }
 }
@@ -0,0 +1,16 @@
+use std::{env, fs};
+
+use dec03::{GearRule, Schematic};
+
+fn main() {
+    let input = include_str!("../../input.txt");
+    let schematic = Schematic::new(input).unwrap();
+    let rule = GearRule::default();
+
+    // `--html out.html` writes a page instead of printing to the terminal
+    let mut args = env::args().skip(1);
+    match (args.next().as_deref(), args.next()) {
+        (Some("--html"), Some(path)) => fs::write(path, schematic.render_html(&rule)).unwrap(),
+        _ => print!("{}", schematic.render_ansi(&rule)),
+    }
+}
@@ -1,171 +1,848 @@
-#[derive(Debug, Clone, Copy)]
-struct SchematicCell {
-    value: char,
-    visited: bool,
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead},
+    ops::Range,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartNumber {
+    pub value: u64,
+    pub row: usize,
+    pub col_span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbol {
+    pub ch: char,
+    pub row: usize,
+    pub col: usize,
 }
 
-impl SchematicCell {
-    fn new(value: char) -> Self {
+impl Symbol {
+    /// The cells sharing an edge with this one, and with `diagonals` also
+    /// those sharing a corner.
+    fn neighbours(&self, diagonals: bool) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (-1_isize..=1)
+            .flat_map(|d_row| (-1_isize..=1).map(move |d_col| (d_row, d_col)))
+            .filter(|&offset| offset != (0, 0))
+            .filter(move |(d_row, d_col)| diagonals || d_row.abs() + d_col.abs() == 1)
+            .filter_map(|(d_row, d_col)| {
+                Some((
+                    self.row.checked_add_signed(d_row)?,
+                    self.col.checked_add_signed(d_col)?,
+                ))
+            })
+    }
+
+    /// Whether any digit of `number` is within `radius` rows and columns,
+    /// worked out from its span so a huge radius costs no more than a small
+    /// one.
+    fn within(&self, number: &PartNumber, radius: usize) -> bool {
+        self.row.abs_diff(number.row) <= radius
+            && number.col_span.start <= self.col.saturating_add(radius)
+            && self.col.saturating_sub(radius) < number.col_span.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    Four,
+    Eight,
+    /// Every cell within `n` rows and `n` columns.
+    Radius(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    fn accepts(self, count: usize) -> bool {
+        match self {
+            Self::Exactly(n) => count == n,
+            Self::AtLeast(n) => count >= n,
+            Self::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Product,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    /// Sums and products saturate at `u128::MAX`. Two numbers always fit.
+    fn apply(self, values: impl Iterator<Item = u64>) -> u128 {
+        let values = values.map(u128::from);
+        match self {
+            Self::Sum => values.fold(0, u128::saturating_add),
+            Self::Product => values.fold(1, u128::saturating_mul),
+            Self::Min => values.min().unwrap_or(0),
+            Self::Max => values.max().unwrap_or(0),
+        }
+    }
+}
+
+/// Which symbols count as gears and how the numbers around them combine. The
+/// default is the puzzle's: a `*` touching exactly two numbers, multiplied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GearRule {
+    pub symbols: Vec<char>,
+    pub arity: Arity,
+    pub neighbourhood: Neighbourhood,
+    pub aggregate: Aggregate,
+}
+
+impl Default for GearRule {
+    fn default() -> Self {
         Self {
-            value,
-            visited: false,
+            symbols: vec!['*'],
+            arity: Arity::Exactly(2),
+            neighbourhood: Neighbourhood::Eight,
+            aggregate: Aggregate::Product,
         }
     }
+}
+
+#[derive(Debug)]
+pub enum SchematicError {
+    Io(io::Error),
+    /// A number starting at this cell that does not fit in a `u64`.
+    NumberTooLarge {
+        row: usize,
+        col: usize,
+    },
+}
 
-    fn is_symbol(&self) -> bool {
-        !self.value.is_ascii_digit() && self.value != '.'
+impl Display for SchematicError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::NumberTooLarge { row, col } => {
+                write!(f, "row {row}, column {col}: number too large")
+            }
+        }
     }
+}
 
-    fn is_unvisited_digit(&self) -> bool {
-        self.value.is_ascii_digit() && !self.visited
+impl From<io::Error> for SchematicError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
     }
 }
 
-fn get_num(row: &mut Vec<SchematicCell>, col_num: usize) -> Option<String> {
-    let cell = row.get_mut(col_num).unwrap();
-    if cell.is_unvisited_digit() {
-        cell.visited = true;
-        let mut num = cell.value.to_string();
-        if col_num > 0 {
-            if let Some(val) = get_num(row, col_num - 1) {
-                num = val + &num;
+/// Every number and symbol in an engine schematic, along with which numbers
+/// each symbol touches.
+#[derive(Debug, Clone, Default)]
+pub struct Schematic {
+    lines: Vec<String>,
+    numbers: Vec<PartNumber>,
+    symbols: Vec<Symbol>,
+    cells: HashMap<(usize, usize), usize>,
+    symbol_numbers: Vec<Vec<usize>>,
+    number_symbols: Vec<Vec<usize>>,
+}
+
+impl Schematic {
+    pub fn new(input: &str) -> Result<Self, SchematicError> {
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+
+        for (row, line) in input.lines().enumerate() {
+            let mut current: Option<PartNumber> = None;
+            for (col, ch) in line.chars().enumerate() {
+                if let Some(digit) = ch.to_digit(10) {
+                    let number = current.get_or_insert(PartNumber {
+                        value: 0,
+                        row,
+                        col_span: col..col,
+                    });
+                    number.value = number
+                        .value
+                        .checked_mul(10)
+                        .and_then(|value| value.checked_add(u64::from(digit)))
+                        .ok_or(SchematicError::NumberTooLarge {
+                            row,
+                            col: number.col_span.start,
+                        })?;
+                    number.col_span.end = col + 1;
+                    continue;
+                }
+                numbers.extend(current.take());
+                if ch != '.' {
+                    symbols.push(Symbol { ch, row, col });
+                }
             }
+            numbers.extend(current);
         }
-        if col_num + 1 < row.len() {
-            if let Some(val) = get_num(row, col_num + 1) {
-                num += &val;
+
+        let mut cells = HashMap::new();
+        for (idx, number) in numbers.iter().enumerate() {
+            for col in number.col_span.clone() {
+                cells.insert((number.row, col), idx);
             }
         }
 
-        Some(num)
-    } else {
-        None
+        let mut number_symbols = vec![vec![]; numbers.len()];
+        let symbol_numbers = symbols
+            .iter()
+            .enumerate()
+            .map(|(symbol_idx, symbol)| {
+                let adjacent = adjacent_numbers(&cells, &numbers, symbol, Neighbourhood::Eight);
+                for &number_idx in &adjacent {
+                    number_symbols[number_idx].push(symbol_idx);
+                }
+                adjacent
+            })
+            .collect();
+
+        Ok(Self {
+            lines: input.lines().map(str::to_string).collect(),
+            numbers,
+            symbols,
+            cells,
+            symbol_numbers,
+            number_symbols,
+        })
     }
-}
 
-pub fn part1(input: &str) {
-    let mut grid: Vec<Vec<SchematicCell>> = input
-        .lines()
-        .map(|line| line.chars().map(SchematicCell::new).collect())
-        .collect();
+    pub fn numbers(&self) -> &[PartNumber] {
+        &self.numbers
+    }
 
-    let mut nums: Vec<i32> = vec![];
-
-    for row_num in 0..grid.len() {
-        let mut row_nums = vec![];
-        for col_num in 0..grid[row_num].len() {
-            if grid[row_num][col_num].is_symbol() {
-                // Row Above
-                if let Some(prev) = grid.get_mut(row_num - 1) {
-                    if let Some(num) = get_num(prev, col_num - 1) {
-                        // Top L
-                        row_nums.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(prev, col_num) {
-                        // Top
-                        row_nums.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(prev, col_num + 1) {
-                        // Top R
-                        row_nums.push(num.parse().unwrap());
-                    }
-                }
-                // This Row
-                {
-                    let row = &mut grid[row_num];
-                    if let Some(num) = get_num(row, col_num - 1) {
-                        // L
-                        row_nums.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(row, col_num + 1) {
-                        // R
-                        row_nums.push(num.parse().unwrap());
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn numbers_next_to(&self, symbol_idx: usize) -> impl Iterator<Item = &PartNumber> {
+        self.symbol_numbers[symbol_idx]
+            .iter()
+            .map(|&idx| &self.numbers[idx])
+    }
+
+    pub fn symbols_next_to(&self, number_idx: usize) -> impl Iterator<Item = &Symbol> {
+        self.number_symbols[number_idx]
+            .iter()
+            .map(|&idx| &self.symbols[idx])
+    }
+
+    /// Numbers that touch at least one symbol.
+    pub fn part_numbers(&self) -> impl Iterator<Item = &PartNumber> {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number)
+    }
+
+    /// Each symbol matching `rule`, with the aggregate of the numbers around
+    /// it. Adjacency for anything but the 8-neighbourhood is worked out here
+    /// rather than precomputed.
+    pub fn gears<'a>(&'a self, rule: &'a GearRule) -> impl Iterator<Item = (&'a Symbol, u128)> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| rule.symbols.contains(&symbol.ch))
+            .filter_map(|(idx, symbol)| {
+                let adjacent = match rule.neighbourhood {
+                    Neighbourhood::Eight => self.symbol_numbers[idx].clone(),
+                    neighbourhood => {
+                        adjacent_numbers(&self.cells, &self.numbers, symbol, neighbourhood)
                     }
+                };
+                if !rule.arity.accepts(adjacent.len()) {
+                    return None;
                 }
-                // Row Below
-                if let Some(next) = grid.get_mut(row_num + 1) {
-                    if let Some(num) = get_num(next, col_num - 1) {
-                        // Bottom L
-                        row_nums.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(next, col_num) {
-                        // Bottom
-                        row_nums.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(next, col_num + 1) {
-                        // Bottom R
-                        row_nums.push(num.parse().unwrap());
+                let values = adjacent.iter().map(|&idx| self.numbers[idx].value);
+                Some((symbol, rule.aggregate.apply(values)))
+            })
+    }
+
+    /// Saturates at `u128::MAX`, like the aggregates it adds up.
+    pub fn gear_total(&self, rule: &GearRule) -> u128 {
+        self.gears(rule)
+            .map(|(_, value)| value)
+            .fold(0, u128::saturating_add)
+    }
+
+    /// Each row of the schematic split into runs of cells of the same kind,
+    /// plus the ratios of the gears on that row.
+    fn annotated_rows(&self, rule: &GearRule) -> Vec<AnnotatedRow> {
+        let gears: HashMap<(usize, usize), u128> = self
+            .gears(rule)
+            .map(|(symbol, ratio)| ((symbol.row, symbol.col), ratio))
+            .collect();
+        let lonely: HashSet<(usize, usize)> = self
+            .symbols
+            .iter()
+            .zip(&self.symbol_numbers)
+            .filter(|(_, numbers)| numbers.is_empty())
+            .map(|(symbol, _)| (symbol.row, symbol.col))
+            .collect();
+
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(row, line)| {
+                let mut runs: Vec<(CellKind, String)> = vec![];
+                for (col, ch) in line.chars().enumerate() {
+                    let kind = if let Some(&idx) = self.cells.get(&(row, col)) {
+                        if self.number_symbols[idx].is_empty() {
+                            CellKind::Uncounted
+                        } else {
+                            CellKind::Counted
+                        }
+                    } else if ch == '.' {
+                        CellKind::Blank
+                    } else if gears.contains_key(&(row, col)) {
+                        CellKind::Gear
+                    } else if lonely.contains(&(row, col)) {
+                        CellKind::Lonely
+                    } else {
+                        CellKind::Symbol
+                    };
+                    match runs.last_mut() {
+                        Some((last_kind, text)) if *last_kind == kind => text.push(ch),
+                        _ => runs.push((kind, ch.to_string())),
                     }
                 }
+
+                let mut row_gears: Vec<(usize, u128)> = gears
+                    .iter()
+                    .filter(|((gear_row, _), _)| *gear_row == row)
+                    .map(|(&(_, col), &ratio)| (col, ratio))
+                    .collect();
+                row_gears.sort_unstable();
+                (
+                    runs,
+                    row_gears.into_iter().map(|(_, ratio)| ratio).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// The schematic coloured for a terminal, with the ratio of each gear
+    /// listed after its row.
+    pub fn render_ansi(&self, rule: &GearRule) -> String {
+        let mut out = String::new();
+        for (runs, ratios) in self.annotated_rows(rule) {
+            for (kind, text) in runs {
+                out += &format!("{}{text}\x1b[0m", kind.ansi());
+            }
+            for ratio in ratios {
+                out += &format!("  {}{ratio}\x1b[0m", CellKind::Gear.ansi());
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The schematic as a standalone HTML page.
+    pub fn render_html(&self, rule: &GearRule) -> String {
+        let mut out = String::from(HTML_HEADER);
+        for (runs, ratios) in self.annotated_rows(rule) {
+            for (kind, text) in runs {
+                out += &format!(
+                    "<span class=\"{}\">{}</span>",
+                    kind.class(),
+                    escape_html(&text)
+                );
+            }
+            for ratio in ratios {
+                out += &format!("  <span class=\"gear\">{ratio}</span>");
             }
+            out.push('\n');
+        }
+        out += "</pre>\n</body>\n</html>\n";
+        out
+    }
+}
+
+type AnnotatedRow = (Vec<(CellKind, String)>, Vec<u128>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+    Blank,
+    Counted,
+    Uncounted,
+    Gear,
+    Lonely,
+    Symbol,
+}
+
+impl CellKind {
+    fn ansi(self) -> &'static str {
+        match self {
+            Self::Blank => "\x1b[2m",
+            Self::Counted => "\x1b[32m",
+            Self::Uncounted => "\x1b[31m",
+            Self::Gear => "\x1b[1;33m",
+            Self::Lonely => "\x1b[35m",
+            Self::Symbol => "\x1b[1m",
         }
-        nums.append(&mut row_nums);
     }
 
-    let sum: i32 = nums.iter().sum();
-    println!("{sum}")
+    fn class(self) -> &'static str {
+        match self {
+            Self::Blank => "blank",
+            Self::Counted => "counted",
+            Self::Uncounted => "uncounted",
+            Self::Gear => "gear",
+            Self::Lonely => "lonely",
+            Self::Symbol => "symbol",
+        }
+    }
+}
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Engine schematic</title>
+<style>
+body { background: #111; color: #ccc; font-family: monospace; }
+.blank { color: #555; }
+.counted { color: #4c4; }
+.uncounted { color: #e44; }
+.gear { color: #fd3; font-weight: bold; }
+.lonely { color: #c6c; }
+.symbol { color: #fff; font-weight: bold; }
+</style>
+</head>
+<body>
+<p>
+<span class="counted">part number</span>
+<span class="uncounted">not a part number</span>
+<span class="gear">gear (ratio after the row)</span>
+<span class="lonely">symbol with no numbers</span>
+<span class="symbol">other symbol</span>
+</p>
+<pre>
+"#;
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
 }
 
-pub fn part2(input: &str) {
-    let mut grid: Vec<Vec<SchematicCell>> = input
-        .lines()
-        .map(|line| line.chars().map(SchematicCell::new).collect())
+fn adjacent_numbers(
+    cells: &HashMap<(usize, usize), usize>,
+    numbers: &[PartNumber],
+    symbol: &Symbol,
+    neighbourhood: Neighbourhood,
+) -> Vec<usize> {
+    let diagonals = match neighbourhood {
+        Neighbourhood::Four => false,
+        Neighbourhood::Eight => true,
+        Neighbourhood::Radius(radius) => {
+            return (0..numbers.len())
+                .filter(|&idx| symbol.within(&numbers[idx], radius))
+                .collect();
+        }
+    };
+    let mut adjacent: Vec<usize> = symbol
+        .neighbours(diagonals)
+        .filter_map(|cell| cells.get(&cell).copied())
         .collect();
+    adjacent.sort_unstable();
+    adjacent.dedup();
+    adjacent
+}
 
-    let mut nums: Vec<i32> = vec![];
-
-    for row_num in 0..grid.len() {
-        for col_num in 0..grid[row_num].len() {
-            if grid[row_num][col_num].value == '*' {
-                let mut gears = vec![];
-                // Row Above
-                if let Some(prev) = grid.get_mut(row_num - 1) {
-                    if let Some(num) = get_num(prev, col_num - 1) {
-                        // Top L
-                        gears.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(prev, col_num) {
-                        // Top
-                        gears.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(prev, col_num + 1) {
-                        // Top R
-                        gears.push(num.parse().unwrap());
-                    }
-                }
-                // This Row
-                {
-                    let row = &mut grid[row_num];
-                    if let Some(num) = get_num(row, col_num - 1) {
-                        // L
-                        gears.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(row, col_num + 1) {
-                        // R
-                        gears.push(num.parse().unwrap());
-                    }
-                }
-                // Row Below
-                if let Some(next) = grid.get_mut(row_num + 1) {
-                    if let Some(num) = get_num(next, col_num - 1) {
-                        // Bottom L
-                        gears.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(next, col_num) {
-                        // Bottom
-                        gears.push(num.parse().unwrap());
-                    }
-                    if let Some(num) = get_num(next, col_num + 1) {
-                        // Bottom R
-                        gears.push(num.parse().unwrap());
-                    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    PartNumber(PartNumber),
+    Gear { symbol: Symbol, ratio: u128 },
+}
+
+fn is_symbol(byte: u8) -> bool {
+    !byte.is_ascii_digit() && byte != b'.'
+}
+
+/// Reads the next row into `buf`, returning `false` once the input is done.
+fn read_row<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(false);
+    }
+    if buf.ends_with(b"\n") {
+        buf.pop();
+    }
+    if buf.ends_with(b"\r") {
+        buf.pop();
+    }
+    Ok(true)
+}
+
+/// The numbers in `row` that have a digit in one of the columns around `col`.
+/// The row must already have passed [`check_numbers`].
+fn numbers_around(row: &[u8], col: usize) -> Vec<u64> {
+    let mut numbers = vec![];
+    let mut next_col = col.saturating_sub(1);
+    while next_col <= col + 1 && next_col < row.len() {
+        if !row[next_col].is_ascii_digit() {
+            next_col += 1;
+            continue;
+        }
+        let mut start = next_col;
+        while start > 0 && row[start - 1].is_ascii_digit() {
+            start -= 1;
+        }
+        let end = row[next_col..]
+            .iter()
+            .position(|byte| !byte.is_ascii_digit())
+            .map_or(row.len(), |len| next_col + len);
+        numbers.push(parse_number(&row[start..end]).expect("checked when read"));
+        next_col = end;
+    }
+    numbers
+}
+
+fn parse_number(digits: &[u8]) -> Option<u64> {
+    digits.iter().try_fold(0u64, |value, digit| {
+        value.checked_mul(10)?.checked_add(u64::from(digit - b'0'))
+    })
+}
+
+/// Makes sure every number in a freshly read row fits in a `u64`, so the rows
+/// around it can parse its numbers without checking again.
+fn check_numbers(bytes: &[u8], row: usize) -> Result<(), SchematicError> {
+    let mut col = 0;
+    while col < bytes.len() {
+        let len = bytes[col..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        if len > 0 && parse_number(&bytes[col..col + len]).is_none() {
+            return Err(SchematicError::NumberTooLarge { row, col });
+        }
+        col += len.max(1);
+    }
+    Ok(())
+}
+
+fn evaluate_row(row: usize, window: &[Vec<u8>; 3], emit: &mut impl FnMut(Event)) {
+    let [above, current, below] = window;
+
+    let mut col = 0;
+    while col < current.len() {
+        if !current[col].is_ascii_digit() {
+            if current[col] == b'*' {
+                let numbers: Vec<u64> = window
+                    .iter()
+                    .flat_map(|window_row| numbers_around(window_row, col))
+                    .collect();
+                if numbers.len() == 2 {
+                    let symbol = Symbol { ch: '*', row, col };
+                    let ratio = numbers.iter().map(|&value| u128::from(value)).product();
+                    emit(Event::Gear { symbol, ratio });
                 }
-                if gears.len() == 2 {
-                    nums.push(gears.iter().product())
+            }
+            col += 1;
+            continue;
+        }
+
+        let end = current[col..]
+            .iter()
+            .position(|byte| !byte.is_ascii_digit())
+            .map_or(current.len(), |len| col + len);
+        let touches_symbol = [above, current, below].iter().any(|window_row| {
+            let from = col.saturating_sub(1).min(window_row.len());
+            let to = (end + 1).min(window_row.len());
+            window_row[from..to].iter().copied().any(is_symbol)
+        });
+        if touches_symbol {
+            emit(Event::PartNumber(PartNumber {
+                value: parse_number(&current[col..end]).expect("checked when read"),
+                row,
+                col_span: col..end,
+            }));
+        }
+        col = end;
+    }
+}
+
+/// Evaluates an ASCII schematic while holding only three rows of it, calling
+/// `emit` for each part number and gear once the row below it has been read.
+pub fn stream_schematic<R: BufRead>(
+    mut reader: R,
+    mut emit: impl FnMut(Event),
+) -> Result<(), SchematicError> {
+    // Above, current and below; rows past either edge are left empty
+    let mut window = [vec![], vec![], vec![]];
+    let mut more = read_row(&mut reader, &mut window[2])?;
+    check_numbers(&window[2], 0)?;
+    let mut row = 0;
+
+    while more {
+        window.rotate_left(1);
+        more = read_row(&mut reader, &mut window[2])?;
+        check_numbers(&window[2], row + 1)?;
+        evaluate_row(row, &window, &mut emit);
+        row += 1;
+    }
+    Ok(())
+}
+
+/// Sums of the part numbers and of the gear ratios in a streamed schematic.
+/// The gear sum saturates like [`Schematic::gear_total`].
+pub fn stream_totals<R: BufRead>(reader: R) -> Result<(u128, u128), SchematicError> {
+    let mut part_sum = 0;
+    let mut gear_sum: u128 = 0;
+    stream_schematic(reader, |event| match event {
+        Event::PartNumber(number) => part_sum += u128::from(number.value),
+        Event::Gear { ratio, .. } => gear_sum = gear_sum.saturating_add(ratio),
+    })?;
+    Ok((part_sum, gear_sum))
+}
+
+pub fn part1(input: &str) -> u128 {
+    let sum = Schematic::new(input)
+        .unwrap()
+        .part_numbers()
+        .map(|number| u128::from(number.value))
+        .sum();
+    println!("{sum}");
+    sum
+}
+
+pub fn part2(input: &str) -> u128 {
+    let sum = Schematic::new(input)
+        .unwrap()
+        .gear_total(&GearRule::default());
+    println!("{sum}");
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        part1, part2, stream_schematic, stream_totals, Aggregate, Arity, Event, GearRule,
+        Neighbourhood, PartNumber, Schematic, SchematicError, Symbol,
+    };
+
+    #[test]
+    fn test_part1() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part1(input), 4361);
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part2(input), 467835);
+    }
+
+    #[test]
+    fn test_edges() {
+        let schematic = Schematic::new("*12\n...\n3.#").unwrap();
+
+        assert_eq!(
+            schematic.numbers(),
+            [
+                PartNumber {
+                    value: 12,
+                    row: 0,
+                    col_span: 1..3
+                },
+                PartNumber {
+                    value: 3,
+                    row: 2,
+                    col_span: 0..1
                 }
+            ]
+        );
+        assert_eq!(
+            schematic.symbols()[1],
+            Symbol {
+                ch: '#',
+                row: 2,
+                col: 2
             }
+        );
+        assert_eq!(schematic.numbers_next_to(0).count(), 1);
+        assert_eq!(schematic.symbols_next_to(1).count(), 0);
+        assert_eq!(part1("*12\n...\n3.#"), 12);
+        assert_eq!(part1("1..\n.*.\n..4"), 5);
+    }
+
+    #[test]
+    fn test_gear_rules() {
+        let schematic = Schematic::new("2.3.\n.#..\n4.5*\n...6").unwrap();
+
+        let rule = GearRule {
+            symbols: vec!['#'],
+            arity: Arity::AtLeast(3),
+            ..Default::default()
+        };
+        assert_eq!(schematic.gear_total(&rule), 2 * 3 * 4 * 5);
+
+        let rule = GearRule {
+            neighbourhood: Neighbourhood::Four,
+            ..rule
+        };
+        assert_eq!(schematic.gear_total(&rule), 0);
+
+        let rule = GearRule {
+            symbols: vec!['#', '*'],
+            arity: Arity::Between(1, 2),
+            neighbourhood: Neighbourhood::Four,
+            aggregate: Aggregate::Max,
+        };
+        assert_eq!(schematic.gear_total(&rule), 6);
+
+        let rule = GearRule {
+            symbols: vec!['#'],
+            arity: Arity::Exactly(5),
+            neighbourhood: Neighbourhood::Radius(2),
+            aggregate: Aggregate::Sum,
+        };
+        assert_eq!(schematic.gear_total(&rule), 2 + 3 + 4 + 5 + 6);
+
+        let rule = GearRule {
+            aggregate: Aggregate::Min,
+            ..rule
+        };
+        assert_eq!(schematic.gear_total(&rule), 2);
+    }
+
+    #[test]
+    fn test_large_radius() {
+        let schematic = Schematic::new("1*2").unwrap();
+        for radius in [usize::MAX, 1_000_000] {
+            let rule = GearRule {
+                neighbourhood: Neighbourhood::Radius(radius),
+                ..GearRule::default()
+            };
+            assert_eq!(schematic.gear_total(&rule), 2, "radius {radius}");
+        }
+
+        let input = include_str!("../input_simple.txt");
+        let schematic = Schematic::new(input).unwrap();
+        let rule = GearRule::default();
+        let radius_one = GearRule {
+            neighbourhood: Neighbourhood::Radius(1),
+            ..rule.clone()
+        };
+        assert_eq!(
+            schematic.gear_total(&radius_one),
+            schematic.gear_total(&rule)
+        );
+
+        // Every number is in reach of every `*`, so none has exactly two
+        let everything = GearRule {
+            neighbourhood: Neighbourhood::Radius(usize::MAX),
+            ..rule
+        };
+        assert_eq!(schematic.gear_total(&everything), 0);
+    }
+
+    #[test]
+    fn test_stream_matches_schematic() {
+        let inputs = [
+            include_str!("../input_simple.txt"),
+            "*12\n...\n3.#",
+            "1..\n.*.\n..4",
+            "2.3.\n.#..\n4.5*\n...6",
+            "12*34\r\n",
+            "",
+        ];
+        for input in inputs {
+            let (part_sum, gear_sum) = stream_totals(input.as_bytes()).unwrap();
+            assert_eq!(part_sum, part1(input), "{input}");
+            assert_eq!(gear_sum, part2(input), "{input}");
         }
     }
-    let sum: i32 = nums.iter().sum();
-    println!("{sum}")
+
+    #[test]
+    fn test_large_numbers() {
+        let input = "12345678901*\n..........20";
+        let schematic = Schematic::new(input).unwrap();
+        assert_eq!(schematic.numbers()[0].value, 12_345_678_901);
+        assert_eq!(part1(input), 12_345_678_921);
+        assert_eq!(part2(input), 246_913_578_020);
+        assert_eq!(
+            stream_totals(input.as_bytes()).unwrap(),
+            (12_345_678_921, 246_913_578_020)
+        );
+
+        let input = "18446744073709551615*\n...................9";
+        assert_eq!(part2(input), u128::from(u64::MAX) * 9);
+        assert_eq!(
+            stream_totals(input.as_bytes()).unwrap().1,
+            u128::from(u64::MAX) * 9
+        );
+
+        let input = "..\n.*18446744073709551616";
+        assert!(matches!(
+            Schematic::new(input),
+            Err(SchematicError::NumberTooLarge { row: 1, col: 2 })
+        ));
+        assert!(matches!(
+            stream_totals(input.as_bytes()),
+            Err(SchematicError::NumberTooLarge { row: 1, col: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_stream_events() {
+        let mut events = vec![];
+        stream_schematic("1..\n*..\n.22".as_bytes(), |event| events.push(event)).unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::PartNumber(PartNumber {
+                    value: 1,
+                    row: 0,
+                    col_span: 0..1
+                }),
+                Event::Gear {
+                    symbol: Symbol {
+                        ch: '*',
+                        row: 1,
+                        col: 0
+                    },
+                    ratio: 22
+                },
+                Event::PartNumber(PartNumber {
+                    value: 22,
+                    row: 2,
+                    col_span: 1..3
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let schematic = Schematic::new("1...\n.*.&\n3...\n...9").unwrap();
+        let rule = GearRule::default();
+
+        assert_eq!(
+            schematic.render_ansi(&rule),
+            "\x1b[32m1\x1b[0m\x1b[2m...\x1b[0m\n\
+             \x1b[2m.\x1b[0m\x1b[1;33m*\x1b[0m\x1b[2m.\x1b[0m\x1b[35m&\x1b[0m  \x1b[1;33m3\x1b[0m\n\
+             \x1b[32m3\x1b[0m\x1b[2m...\x1b[0m\n\
+             \x1b[2m...\x1b[0m\x1b[31m9\x1b[0m\n"
+        );
+
+        let html = schematic.render_html(&rule);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>\n"));
+        assert!(html.contains(
+            "<span class=\"blank\">.</span><span class=\"gear\">*</span>\
+             <span class=\"blank\">.</span><span class=\"lonely\">&amp;</span>  \
+             <span class=\"gear\">3</span>\n"
+        ));
+    }
 }
@@ -1,69 +1,758 @@
-struct Card {
-    winning_numbers: Vec<i32>,
-    my_numbers: Vec<i32>,
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead},
+    num::NonZeroUsize,
+};
+
+/// A set of card numbers below [`NumberSet::LIMIT`], one bit per number.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct NumberSet {
+    words: [u128; 8],
+}
+
+impl NumberSet {
+    const LIMIT: u32 = 128 * 8;
+
+    /// Adds `num`, returning `false` if it was already there.
+    fn insert(&mut self, num: u32) -> bool {
+        assert!(num < Self::LIMIT, "Card number {num} out of range");
+        let (word, bit) = (num as usize / 128, num % 128);
+        let fresh = self.words[word] & (1 << bit) == 0;
+        self.words[word] |= 1 << bit;
+        fresh
+    }
+
+    fn common(&self, other: &Self) -> usize {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Card {
+    id: u32,
+    winning_numbers: NumberSet,
+    my_numbers: NumberSet,
 }
 
 impl Card {
-    fn new(input: &str) -> Self {
-        let mut nums = input.split(':').last().unwrap().split('|');
+    pub fn id(&self) -> u32 {
+        self.id
+    }
 
-        let winning_numbers: Vec<i32> = parse_num_list(nums.next().unwrap());
+    pub fn matches(&self) -> usize {
+        self.winning_numbers.common(&self.my_numbers)
+    }
+}
 
-        let my_numbers: Vec<i32> = parse_num_list(nums.next().unwrap());
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardError {
+    Malformed {
+        line: usize,
+    },
+    BadToken {
+        line: usize,
+        token: String,
+    },
+    Duplicate {
+        line: usize,
+        number: u32,
+    },
+    TooLarge {
+        line: usize,
+        number: u32,
+    },
+    OutOfSequence {
+        line: usize,
+        expected: u32,
+        found: u32,
+    },
+}
 
-        Self {
-            winning_numbers,
-            my_numbers,
+impl Display for CardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed { line } => write!(f, "line {line}: not a scratchcard"),
+            Self::BadToken { line, token } => write!(f, "line {line}: `{token}` is not a number"),
+            Self::Duplicate { line, number } => write!(f, "line {line}: {number} listed twice"),
+            Self::TooLarge { line, number } => {
+                write!(f, "line {line}: {number} is not below {}", NumberSet::LIMIT)
+            }
+            Self::OutOfSequence {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected card {expected}, found {found}"),
         }
     }
+}
 
-    fn matches(&self) -> usize {
-        self.my_numbers
-            .iter()
-            .filter(|&num| self.winning_numbers.contains(num))
-            .count()
+/// Parses a deck line by line, checking that the cards are numbered 1, 2, 3
+/// and so on.
+#[derive(Debug, Clone, Default)]
+pub struct CardParser {
+    line: usize,
+}
+
+impl CardParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, input: &str) -> Result<Card, CardError> {
+        self.line += 1;
+        let line = self.line;
+
+        let (label, nums) = input.split_once(':').ok_or(CardError::Malformed { line })?;
+        let (winning, mine) = nums.split_once('|').ok_or(CardError::Malformed { line })?;
+
+        let id = label
+            .strip_prefix("Card")
+            .filter(|id| id.starts_with(' '))
+            .ok_or(CardError::Malformed { line })?
+            .trim();
+        let id: u32 = id.parse().map_err(|_| CardError::BadToken {
+            line,
+            token: id.to_string(),
+        })?;
+        if id as usize != line {
+            return Err(CardError::OutOfSequence {
+                line,
+                expected: line as u32,
+                found: id,
+            });
+        }
+
+        Ok(Card {
+            id,
+            winning_numbers: parse_num_list(winning, line)?,
+            my_numbers: parse_num_list(mine, line)?,
+        })
+    }
+}
+
+fn parse_num_list(input: &str, line: usize) -> Result<NumberSet, CardError> {
+    let mut nums = NumberSet::default();
+    for token in input.split_whitespace() {
+        let number = token.parse().map_err(|_| CardError::BadToken {
+            line,
+            token: token.to_string(),
+        })?;
+        if number >= NumberSet::LIMIT {
+            return Err(CardError::TooLarge { line, number });
+        }
+        if !nums.insert(number) {
+            return Err(CardError::Duplicate { line, number });
+        }
+    }
+    Ok(nums)
+}
+
+pub fn cards(input: &str) -> impl Iterator<Item = Result<Card, CardError>> + '_ {
+    let mut parser = CardParser::new();
+    input.lines().map(move |line| parser.parse(line))
+}
+
+/// How many points a card with a given number of matches is worth. Points
+/// that would not fit in a `u64` saturate at `u64::MAX`.
+pub trait Scoring {
+    fn score(&self, matches: usize) -> u64;
+}
+
+/// One point for the first match, doubled for each match after that.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Doubling;
+
+impl Scoring for Doubling {
+    fn score(&self, matches: usize) -> u64 {
+        match matches {
+            0 => 0,
+            matches => 1_u64.checked_shl(matches as u32 - 1).unwrap_or(u64::MAX),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Linear {
+    pub per_match: u64,
+}
+
+impl Scoring for Linear {
+    fn score(&self, matches: usize) -> u64 {
+        self.per_match.saturating_mul(matches as u64)
+    }
+}
+
+/// Points looked up by number of matches. Counts past the end of the table
+/// score the same as the last entry.
+#[derive(Debug, Clone, Default)]
+pub struct Table(pub Vec<u64>);
+
+impl Scoring for Table {
+    fn score(&self, matches: usize) -> u64 {
+        self.0.get(matches).or(self.0.last()).copied().unwrap_or(0)
+    }
+}
+
+/// Which later cards a card wins copies of. Each award is the distance to the
+/// card it lands on and how many copies of it every copy of this card wins.
+/// Awards at distance 0 would have a card win itself, and are ignored.
+pub trait CascadeRule {
+    fn awards(&self, matches: usize) -> Vec<(usize, u64)>;
+}
+
+/// One copy of each of the next `matches` cards.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NextCards;
+
+impl CascadeRule for NextCards {
+    fn awards(&self, matches: usize) -> Vec<(usize, u64)> {
+        (1..=matches).map(|offset| (offset, 1)).collect()
     }
+}
+
+/// One copy of each of `matches` cards, taking only every `n`th card after
+/// this one.
+#[derive(Debug, Clone, Copy)]
+pub struct EveryNthCard(pub NonZeroUsize);
+
+impl CascadeRule for EveryNthCard {
+    fn awards(&self, matches: usize) -> Vec<(usize, u64)> {
+        // Offsets too large for a `usize` are past the last card anyway
+        (1..=matches)
+            .map_while(|idx| idx.checked_mul(self.0.get()))
+            .map(|offset| (offset, 1))
+            .collect()
+    }
+}
+
+/// `matches` copies of each of the next `matches` cards.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeightedByMatches;
+
+impl CascadeRule for WeightedByMatches {
+    fn awards(&self, matches: usize) -> Vec<(usize, u64)> {
+        (1..=matches)
+            .map(|offset| (offset, matches as u64))
+            .collect()
+    }
+}
+
+/// A number of scratchcard copies. Implemented for the unsigned integers and,
+/// with the `bigint` feature, for `BigUint`.
+pub trait CopyCount: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_u64(value: u64) -> Option<Self>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+}
 
-    fn value(&self) -> i32 {
-        let my_wins = self.matches();
+macro_rules! impl_copy_count {
+    ($($ty:ty),*) => {
+        $(
+            impl CopyCount for $ty {
+                fn zero() -> Self {
+                    0
+                }
 
-        if my_wins == 0 {
-            0
-        } else {
-            2_i32.pow(my_wins as u32 - 1)
+                fn one() -> Self {
+                    1
+                }
+
+                fn from_u64(value: u64) -> Option<Self> {
+                    value.try_into().ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$ty>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$ty>::checked_mul(*self, *other)
+                }
+            }
+        )*
+    };
+}
+
+impl_copy_count!(u8, u16, u32, u64, u128, usize);
+
+#[cfg(feature = "bigint")]
+impl CopyCount for num_bigint::BigUint {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn one() -> Self {
+        1_u32.into()
+    }
+
+    fn from_u64(value: u64) -> Option<Self> {
+        Some(value.into())
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+}
+
+#[derive(Debug)]
+pub enum CascadeError {
+    Io(io::Error),
+    Card(CardError),
+    Overflow { line: usize },
+}
+
+impl Display for CascadeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Card(err) => write!(f, "{err}"),
+            Self::Overflow { line } => write!(f, "line {line}: too many copies to count"),
         }
     }
 }
 
-fn parse_num_list(input: &str) -> Vec<i32> {
-    input
-        .split(' ')
-        .filter_map(|e| match e.parse() {
-            Ok(num) => Some(num),
-            Err(_) => None,
+impl From<io::Error> for CascadeError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<CardError> for CascadeError {
+    fn from(err: CardError) -> Self {
+        Self::Card(err)
+    }
+}
+
+pub fn cascade<C: CopyCount, R: BufRead>(reader: R) -> Result<C, CascadeError> {
+    cascade_with(reader, &NextCards)
+}
+
+/// Counts every scratchcard won under `rule`, reading the deck one card at a
+/// time. Only the copies still owed to later cards are kept, one entry per
+/// card, so memory is bounded by how many cards are owed copies rather than by
+/// how far awards reach. Wins that would land past the last card are dropped,
+/// and so never overflow.
+pub fn cascade_with<C, R, Rule>(reader: R, rule: &Rule) -> Result<C, CascadeError>
+where
+    C: CopyCount,
+    R: BufRead,
+    Rule: CascadeRule,
+{
+    // Keyed by the line of the card the copies are owed to. `None` marks a
+    // count that overflowed, which is only an error if that card turns up.
+    let mut owed: BTreeMap<usize, Option<C>> = BTreeMap::new();
+    let mut total = C::zero();
+
+    let mut parser = CardParser::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let line_num = idx + 1;
+        let card = parser.parse(&line?)?;
+        let overflow = || CascadeError::Overflow { line: line_num };
+
+        let copies = owed
+            .remove(&line_num)
+            .unwrap_or_else(|| Some(C::zero()))
+            .and_then(|copies| copies.checked_add(&C::one()))
+            .ok_or_else(overflow)?;
+        total = total.checked_add(&copies).ok_or_else(overflow)?;
+
+        for (offset, weight) in rule.awards(card.matches()) {
+            let target = match line_num.checked_add(offset) {
+                Some(target) if offset > 0 => target,
+                _ => continue,
+            };
+            let won = C::from_u64(weight).and_then(|weight| copies.checked_mul(&weight));
+            let owed_copies = owed.entry(target).or_insert_with(|| Some(C::zero()));
+            *owed_copies = owed_copies
+                .take()
+                .zip(won)
+                .and_then(|(owed_copies, won)| owed_copies.checked_add(&won));
+        }
+    }
+
+    Ok(total)
+}
+
+/// How many copies a card ended up with, and which earlier cards they came
+/// from. The original card is counted in `copies` but has no source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardProvenance {
+    pub id: u32,
+    pub copies: u64,
+    pub sources: Vec<(u32, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub cards: Vec<CardProvenance>,
+}
+
+impl Provenance {
+    pub fn total(&self) -> u64 {
+        self.cards.iter().map(|card| card.copies).sum()
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (u32, u32, u64)> + '_ {
+        self.cards.iter().flat_map(|card| {
+            card.sources
+                .iter()
+                .map(move |&(source, copies)| (source, card.id, copies))
         })
-        .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cascade {\n");
+        for card in &self.cards {
+            out += &format!(
+                "    {} [label=\"Card {}\\n{} copies\"];\n",
+                card.id, card.id, card.copies
+            );
+        }
+        for (from, to, copies) in self.edges() {
+            out += &format!("    {from} -> {to} [label=\"{copies}\", weight={copies}];\n");
+        }
+        out += "}\n";
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .cards
+            .iter()
+            .map(|card| format!("{{\"id\":{},\"copies\":{}}}", card.id, card.copies))
+            .collect();
+        let edges: Vec<String> = self
+            .edges()
+            .map(|(from, to, copies)| {
+                format!("{{\"from\":{from},\"to\":{to},\"copies\":{copies}}}")
+            })
+            .collect();
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}]}}",
+            nodes.join(","),
+            edges.join(",")
+        )
+    }
 }
 
-pub fn part1(input: &str) {
-    let sum: i32 = input.lines().map(Card::new).map(|card| card.value()).sum();
+pub fn provenance(input: &str) -> Result<Provenance, CascadeError> {
+    provenance_with(input, &NextCards)
+}
+
+/// Runs the cascade under `rule`, recording every award. Unlike
+/// [`cascade_with`] this keeps the whole deck in memory.
+pub fn provenance_with<Rule: CascadeRule>(
+    input: &str,
+    rule: &Rule,
+) -> Result<Provenance, CascadeError> {
+    let deck: Vec<Card> = cards(input).collect::<Result<_, _>>()?;
+    let mut cards: Vec<CardProvenance> = deck
+        .iter()
+        .map(|card| CardProvenance {
+            id: card.id,
+            copies: 1,
+            sources: vec![],
+        })
+        .collect();
+
+    for (idx, card) in deck.iter().enumerate() {
+        let overflow = || CascadeError::Overflow { line: idx + 1 };
+        let copies = cards[idx].copies;
+
+        for (offset, weight) in rule.awards(card.matches()) {
+            let target = match idx.checked_add(offset) {
+                Some(target) if offset > 0 => cards.get_mut(target),
+                _ => None,
+            };
+            let Some(target) = target else {
+                continue;
+            };
+            let won = copies.checked_mul(weight).ok_or_else(overflow)?;
+            target.copies = target.copies.checked_add(won).ok_or_else(overflow)?;
+            target.sources.push((card.id, won));
+        }
+    }
+
+    Ok(Provenance { cards })
+}
+
+/// The points for every card, saturating at `u64::MAX` like the scores
+/// themselves.
+pub fn score<S: Scoring>(input: &str, scoring: &S) -> Result<u64, CardError> {
+    cards(input).try_fold(0_u64, |sum, card| {
+        Ok(sum.saturating_add(scoring.score(card?.matches())))
+    })
+}
+
+pub fn part1(input: &str) -> u64 {
+    let sum = score(input, &Doubling).unwrap();
     println!("{sum}");
+    sum
 }
 
-pub fn part2(input: &str) {
-    let cards: Vec<Card> = input.lines().map(Card::new).collect();
-    let mut copies: Vec<usize> = vec![1; cards.len()];
+pub fn part2(input: &str) -> u64 {
+    let total = cascade(input.as_bytes()).unwrap();
 
-    for i in 0..cards.len() {
-        let card = cards.get(i).unwrap();
-        let card_copies = copies.get(i).unwrap().to_owned();
-        let matches = card.matches();
+    println!("{total}");
+    total
+}
 
-        for match_num in 1..=matches {
-            copies[i + match_num] += card_copies
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cards, cascade, cascade_with, part1, part2, provenance, provenance_with, score, CardError,
+        CardProvenance, CascadeError, CascadeRule, Doubling, EveryNthCard, Linear, NumberSet,
+        Scoring, Table, WeightedByMatches,
+    };
+    use std::num::NonZeroUsize;
+
+    /// A deck where every card matches all the cards after it, so each card
+    /// has twice as many copies as the one before.
+    fn doubling_deck(cards: usize) -> String {
+        (1..=cards)
+            .map(|id| {
+                let nums: Vec<String> = (1..=cards - id).map(|num| num.to_string()).collect();
+                format!("Card {id}: {} | {}\n", nums.join(" "), nums.join(" "))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_part1() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part1(input), 13);
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part2(input), 30);
+    }
+
+    #[test]
+    fn test_wins_past_last_card() {
+        let total: u64 = cascade("Card 1: 1 2 3 | 1 2 3".as_bytes()).unwrap();
+        assert_eq!(total, 1);
+    }
+
+    #[test]
+    fn test_cascade_errors() {
+        let deck = doubling_deck(9);
+        assert_eq!(cascade::<u16, _>(deck.as_bytes()).unwrap(), 511);
+        assert!(matches!(
+            cascade::<u8, _>(deck.as_bytes()),
+            Err(CascadeError::Overflow { line: 9 })
+        ));
+
+        let deck = "Card 1: 1 | 2\nCard 2 1 2\n";
+        assert!(matches!(
+            cascade::<u64, _>(deck.as_bytes()),
+            Err(CascadeError::Card(CardError::Malformed { line: 2 }))
+        ));
+    }
+
+    #[test]
+    fn test_overflow_past_last_card() {
+        // Card 2 wins 17 * 16 copies of each of cards 3 to 18, which would
+        // overflow a `u8`, but none of those cards exist
+        let nums: Vec<String> = (1..=16).map(|num| num.to_string()).collect();
+        let card = format!("{} | {}", nums.join(" "), nums.join(" "));
+        let deck = format!("Card 1: {card}\nCard 2: {card}\n");
+
+        let total: u8 = cascade_with(deck.as_bytes(), &WeightedByMatches).unwrap();
+        assert_eq!(total, 18);
+        let tree = provenance_with(&deck, &WeightedByMatches).unwrap();
+        assert_eq!(tree.total(), u64::from(total));
+
+        let deck = format!("{deck}Card 3: 1 | 2\n");
+        assert!(matches!(
+            cascade_with::<u8, _, _>(deck.as_bytes(), &WeightedByMatches),
+            Err(CascadeError::Overflow { line: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_wide_counts() {
+        let deck = doubling_deck(100);
+        let total: u128 = cascade(deck.as_bytes()).unwrap();
+        assert_eq!(total, u128::MAX >> 28);
+        assert!(matches!(
+            cascade::<u64, _>(deck.as_bytes()),
+            Err(CascadeError::Overflow { .. })
+        ));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_counts() {
+        use num_bigint::BigUint;
+
+        let deck = doubling_deck(200);
+        let total: BigUint = cascade(deck.as_bytes()).unwrap();
+        assert_eq!(total, (BigUint::from(1_u32) << 200) - 1_u32);
+    }
+
+    #[test]
+    fn test_scoring() {
+        assert_eq!(Doubling.score(0), 0);
+        assert_eq!(Doubling.score(4), 8);
+        assert_eq!(Doubling.score(100), u64::MAX);
+
+        let nums: Vec<String> = (1..=70).map(|num| num.to_string()).collect();
+        let card = format!("{} | {}", nums.join(" "), nums.join(" "));
+        let input = format!("Card 1: {card}\nCard 2: {card}\n");
+        assert_eq!(score(&input, &Doubling).unwrap(), u64::MAX);
+
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(
+            score(input, &Linear { per_match: 3 }).unwrap(),
+            3 * (4 + 2 + 2 + 1)
+        );
+        assert_eq!(
+            score(input, &Table(vec![0, 10, 20, 50])).unwrap(),
+            50 + 20 + 20 + 10
+        );
+    }
+
+    #[test]
+    fn test_cascade_rules() {
+        let input = include_str!("../input_simple.txt");
+
+        // Copies per card are 1, 5, 15, 45, 80 and 1: card 1 gives 4 copies
+        // to each of cards 2 to 5, card 2 gives 2 * 5 to cards 3 and 4, and so on
+        let weighted: u64 = cascade_with(input.as_bytes(), &WeightedByMatches).unwrap();
+        assert_eq!(weighted, 1 + 5 + 15 + 45 + 80 + 1);
+
+        // Card 1 wins 3 and 5 plus two cards past the end; card 2 wins 4 and 6;
+        // card 3 wins 5 and 7, card 4 wins 6.
+        let every_other = EveryNthCard(NonZeroUsize::new(2).unwrap());
+        let every_other: u64 = cascade_with(input.as_bytes(), &every_other).unwrap();
+        assert_eq!(every_other, 1 + 1 + 2 + 2 + 4 + 4);
+
+        let far = EveryNthCard(NonZeroUsize::MAX);
+        assert_eq!(far.awards(3), vec![(usize::MAX, 1)]);
+        let far_total: u64 = cascade_with("Card 1: 1 | 1".as_bytes(), &far).unwrap();
+        assert_eq!(far_total, 1);
+    }
+
+    #[test]
+    fn test_self_awards_ignored() {
+        struct ThisAndNext;
+
+        impl CascadeRule for ThisAndNext {
+            fn awards(&self, matches: usize) -> Vec<(usize, u64)> {
+                (0..=matches).map(|offset| (offset, 1)).collect()
+            }
         }
+
+        let input = include_str!("../input_simple.txt");
+        let total: u64 = cascade_with(input.as_bytes(), &ThisAndNext).unwrap();
+        assert_eq!(total, 30);
+        assert_eq!(provenance_with(input, &ThisAndNext).unwrap().total(), 30);
     }
-    let total: usize = copies.iter().sum();
 
-    println!("{total}")
+    #[test]
+    fn test_number_set() {
+        let mut set = NumberSet::default();
+        assert!(set.insert(5));
+        assert!(set.insert(300));
+        assert!(!set.insert(5));
+
+        let mut other = NumberSet::default();
+        other.insert(300);
+        other.insert(6);
+        assert_eq!(set.common(&other), 1);
+        assert_eq!(other.common(&set), 1);
+    }
+
+    #[test]
+    fn test_card_validation() {
+        let card = cards("Card  1: 41 48 | 48 41 200").next().unwrap().unwrap();
+        assert_eq!(card.id(), 1);
+        assert_eq!(card.matches(), 2);
+
+        let errors: Vec<CardError> =
+            cards("Card 1: 1 2 | 3\nCard 3: 1 | 2\nCard 3: 1 x | 2\nCard 4: 1 | 2 2\nCard 5 1 | 2\nCard 6: 4000000000 | 1")
+                .filter_map(Result::err)
+                .collect();
+        assert_eq!(
+            errors,
+            vec![
+                CardError::OutOfSequence {
+                    line: 2,
+                    expected: 2,
+                    found: 3
+                },
+                CardError::BadToken {
+                    line: 3,
+                    token: "x".to_string()
+                },
+                CardError::Duplicate { line: 4, number: 2 },
+                CardError::Malformed { line: 5 },
+                CardError::TooLarge {
+                    line: 6,
+                    number: 4_000_000_000
+                },
+            ]
+        );
+        assert_eq!(
+            errors[4].to_string(),
+            "line 6: 4000000000 is not below 1024"
+        );
+    }
+
+    #[test]
+    fn test_provenance() {
+        let input = include_str!("../input_simple.txt");
+        let tree = provenance(input).unwrap();
+
+        assert_eq!(tree.total(), 30);
+        assert_eq!(
+            tree.cards[3],
+            CardProvenance {
+                id: 4,
+                copies: 8,
+                sources: vec![(1, 1), (2, 2), (3, 4)]
+            }
+        );
+        assert_eq!(tree.cards[5].sources, vec![]);
+
+        let weighted = provenance_with(input, &WeightedByMatches).unwrap();
+        let total: u64 = cascade_with(input.as_bytes(), &WeightedByMatches).unwrap();
+        assert_eq!(weighted.total(), total);
+    }
+
+    #[test]
+    fn test_provenance_export() {
+        let tree = provenance("Card 1: 1 2 | 1 2\nCard 2: 1 | 1\nCard 3: | 1").unwrap();
+
+        assert_eq!(
+            tree.to_dot(),
+            "digraph cascade {\n\
+             \x20   1 [label=\"Card 1\\n1 copies\"];\n\
+             \x20   2 [label=\"Card 2\\n2 copies\"];\n\
+             \x20   3 [label=\"Card 3\\n4 copies\"];\n\
+             \x20   1 -> 2 [label=\"1\", weight=1];\n\
+             \x20   1 -> 3 [label=\"1\", weight=1];\n\
+             \x20   2 -> 3 [label=\"2\", weight=2];\n\
+             }\n"
+        );
+        assert_eq!(
+            tree.to_json(),
+            "{\"nodes\":[{\"id\":1,\"copies\":1},{\"id\":2,\"copies\":2},{\"id\":3,\"copies\":4}],\
+             \"edges\":[{\"from\":1,\"to\":2,\"copies\":1},{\"from\":1,\"to\":3,\"copies\":1},\
+             {\"from\":2,\"to\":3,\"copies\":2}]}"
+        );
+    }
 }
@@ -0,0 +1,11 @@
+use dec05::parse_almanac;
+
+fn main() {
+    let input = include_str!("../../input.txt");
+    let (_, maps) = parse_almanac(input).unwrap();
+
+    for issue in maps.validate() {
+        eprintln!("{issue}");
+    }
+    print!("{}", maps.compose("seed", "location").unwrap().to_table());
+}
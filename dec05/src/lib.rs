@@ -1,5 +1,14 @@
 use rayon::prelude::*;
-use std::str::Lines;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Display, Formatter},
+    iter::Enumerate,
+    ops::Range,
+    str::Lines,
+};
+
+/// The almanac's lines, numbered from 0.
+type NumberedLines<'a> = Enumerate<Lines<'a>>;
 
 struct Map {
     dest_start: u64,
@@ -7,64 +16,366 @@ struct Map {
     range: u64,
 }
 
-struct MapCollection {
-    seed_to_soil: Vec<Map>,
-    soil_to_fertilizer: Vec<Map>,
-    fertilizer_to_water: Vec<Map>,
-    water_to_light: Vec<Map>,
-    light_to_temperature: Vec<Map>,
-    temperature_to_humidity: Vec<Map>,
-    humidity_to_location: Vec<Map>,
+/// One `x-to-y map:` block of the almanac.
+struct Stage {
+    from: String,
+    to: String,
+    maps: Vec<Map>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlmanacError {
+    BadSeeds(String),
+    /// A seed range start with no length after it.
+    UnpairedSeed(u64),
+    BadHeader(String),
+    BadEntry {
+        line: usize,
+        entry: String,
+    },
+    UnknownCategory(String),
+    Unreachable {
+        from: String,
+        to: String,
+    },
+}
+
+impl Display for AlmanacError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadSeeds(line) => write!(f, "`{line}` is not a `seeds:` line"),
+            Self::UnpairedSeed(start) => write!(f, "seed range at {start} has no length"),
+            Self::BadHeader(line) => write!(f, "`{line}` is not an `x-to-y map:` header"),
+            Self::BadEntry { line, entry } => {
+                write!(f, "line {line}: `{entry}` is not three numbers")
+            }
+            Self::UnknownCategory(category) => write!(f, "no map mentions `{category}`"),
+            Self::Unreachable { from, to } => {
+                write!(f, "no chain of maps leads from {from} to {to}")
+            }
+        }
+    }
+}
+
+/// Something wrong with one stage's map entries. Entries are numbered from 1
+/// in the order they are listed under the stage's header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapIssue {
+    /// Two entries with the same source range, whatever their destinations.
+    Duplicate {
+        stage: String,
+        first: usize,
+        second: usize,
+    },
+    /// Two entries sharing some sources. The earlier entry wins them.
+    Overlap {
+        stage: String,
+        first: usize,
+        second: usize,
+        sources: Range<u64>,
+    },
+    /// An entry with a source or destination that would reach `u64::MAX`. It
+    /// is cut short so that none does.
+    Overflow { stage: String, entry: usize },
+}
+
+impl Display for MapIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate {
+                stage,
+                first,
+                second,
+            } => write!(
+                f,
+                "{stage}: entries {first} and {second} cover the same sources"
+            ),
+            Self::Overlap {
+                stage,
+                first,
+                second,
+                sources,
+            } => write!(
+                f,
+                "{stage}: entries {first} and {second} both cover {}..{}",
+                sources.start, sources.end
+            ),
+            Self::Overflow { stage, entry } => {
+                write!(
+                    f,
+                    "{stage}: entry {entry} reaches {}, so it is cut short",
+                    u64::MAX
+                )
+            }
+        }
+    }
+}
+
+/// The almanac's maps, keyed by the categories named in their headers. The
+/// stages can come in any order, and any category can be converted to any
+/// other that a chain of maps leads to.
+pub struct MapCollection {
+    stages: Vec<Stage>,
 }
 
 impl MapCollection {
-    fn new(lines: &mut Lines<'_>) -> Self {
-        lines.next(); // Throw away blank line
-
-        let seed_to_soil = map_vec(lines);
-        let soil_to_fertilizer = map_vec(lines);
-        let fertilizer_to_water = map_vec(lines);
-        let water_to_light = map_vec(lines);
-        let light_to_temperature = map_vec(lines);
-        let temperature_to_humidity = map_vec(lines);
-        let humidity_to_location = map_vec(lines);
-        Self {
-            seed_to_soil,
-            soil_to_fertilizer,
-            fertilizer_to_water,
-            water_to_light,
-            light_to_temperature,
-            temperature_to_humidity,
-            humidity_to_location,
+    fn new(lines: &mut NumberedLines<'_>) -> Result<Self, AlmanacError> {
+        let mut stages = vec![];
+
+        while let Some((_, header)) = lines.next() {
+            if header.trim().is_empty() {
+                continue;
+            }
+            let (from, to) = header
+                .strip_suffix(" map:")
+                .and_then(|label| label.split_once("-to-"))
+                .ok_or_else(|| AlmanacError::BadHeader(header.to_string()))?;
+
+            stages.push(Stage {
+                from: from.to_string(),
+                to: to.to_string(),
+                maps: map_vec(lines)?,
+            });
+        }
+
+        Ok(Self { stages })
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        let mut categories: Vec<&str> = self
+            .stages
+            .iter()
+            .flat_map(|stage| [stage.from.as_str(), stage.to.as_str()])
+            .collect();
+        categories.sort_unstable();
+        categories.dedup();
+        categories.into_iter()
+    }
+
+    /// The stages to go through, in order, to get from one category to another.
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&Stage>, AlmanacError> {
+        for category in [from, to] {
+            if !self.categories().any(|known| known == category) {
+                return Err(AlmanacError::UnknownCategory(category.to_string()));
+            }
+        }
+
+        // Breadth-first, remembering the stage that first reached each category
+        let mut reached_by: HashMap<&str, Option<&Stage>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                break;
+            }
+            for stage in self.stages.iter().filter(|stage| stage.from == category) {
+                if !reached_by.contains_key(stage.to.as_str()) {
+                    reached_by.insert(&stage.to, Some(stage));
+                    queue.push_back(&stage.to);
+                }
+            }
+        }
+
+        let mut path = vec![];
+        let mut category = to;
+        loop {
+            match reached_by.get(category) {
+                Some(Some(stage)) => {
+                    path.push(*stage);
+                    category = &stage.from;
+                }
+                Some(None) => break,
+                None => {
+                    return Err(AlmanacError::Unreachable {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    })
+                }
+            }
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    pub fn convert(&self, value: u64, from: &str, to: &str) -> Result<u64, AlmanacError> {
+        Ok(self
+            .path(from, to)?
+            .iter()
+            .fold(value, |value, stage| find_dest(value, &stage.maps)))
+    }
+
+    /// `value` and what it maps to in every category on the way to `to`.
+    pub fn trace(&self, value: u64, from: &str, to: &str) -> Result<SeedPath, AlmanacError> {
+        let mut steps = vec![(from.to_string(), value)];
+        let mut value = value;
+        for stage in self.path(from, to)? {
+            value = find_dest(value, &stage.maps);
+            steps.push((stage.to.clone(), value));
+        }
+        Ok(SeedPath { steps })
+    }
+
+    /// Every value of `from` that maps to one of `ranges` in `to`.
+    pub fn invert_ranges(
+        &self,
+        ranges: Vec<Range<u64>>,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Range<u64>>, AlmanacError> {
+        Ok(self
+            .path(from, to)?
+            .iter()
+            .rev()
+            .fold(ranges, |ranges, stage| {
+                find_source_ranges(ranges, &stage.maps)
+            }))
+    }
+
+    /// `u64::MAX` cannot be written as a range here, so it finds nothing.
+    pub fn invert(
+        &self,
+        value: u64,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Range<u64>>, AlmanacError> {
+        let value = value..value.saturating_add(1);
+        self.invert_ranges(vec![value], from, to)
+    }
+
+    pub fn convert_ranges(
+        &self,
+        ranges: Vec<Range<u64>>,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Range<u64>>, AlmanacError> {
+        Ok(self.path(from, to)?.iter().fold(ranges, |ranges, stage| {
+            find_dest_ranges(ranges, &stage.maps)
+        }))
+    }
+
+    /// Every duplicate, overlapping or overflowing entry, stage by stage.
+    /// Lookups still work on an almanac with issues: the first entry covering
+    /// a value wins it, and overflowing entries stop short of `u64::MAX`.
+    pub fn validate(&self) -> Vec<MapIssue> {
+        let mut issues = vec![];
+
+        for stage in &self.stages {
+            let label = || format!("{}-to-{}", stage.from, stage.to);
+            for (idx, map) in stage.maps.iter().enumerate() {
+                if map.overflows() {
+                    issues.push(MapIssue::Overflow {
+                        stage: label(),
+                        entry: idx + 1,
+                    });
+                }
+                for (earlier_idx, earlier) in stage.maps[..idx].iter().enumerate() {
+                    let (first, second) = (earlier_idx + 1, idx + 1);
+                    let (ours, theirs) = (map.sources(), earlier.sources());
+                    let start = ours.start.max(theirs.start);
+                    let end = ours.end.min(theirs.end);
+                    if ours == theirs {
+                        issues.push(MapIssue::Duplicate {
+                            stage: label(),
+                            first,
+                            second,
+                        });
+                    } else if start < end {
+                        issues.push(MapIssue::Overlap {
+                            stage: label(),
+                            first,
+                            second,
+                            sources: start..end,
+                        });
+                    }
+                }
+            }
         }
+
+        issues
+    }
+
+    /// The whole chain of stages from one category to another, flattened into
+    /// a single [`PiecewiseMap`].
+    pub fn compose(&self, from: &str, to: &str) -> Result<PiecewiseMap, AlmanacError> {
+        let mut pieces = vec![Piece {
+            sources: 0..u64::MAX,
+            dest_start: 0,
+        }];
+
+        for stage in self.path(from, to)? {
+            pieces = pieces
+                .into_iter()
+                .flat_map(|piece| {
+                    let offset = piece.sources.start;
+                    let dests = piece.dest_start..piece.get(piece.sources.end - 1) + 1;
+                    split_by_maps(dests.clone(), &stage.maps).into_iter().map(
+                        move |(part, dest_start)| Piece {
+                            sources: offset + (part.start - dests.start)
+                                ..offset + (part.end - dests.start),
+                            dest_start,
+                        },
+                    )
+                })
+                .collect();
+        }
+
+        pieces.sort_unstable_by_key(|piece| piece.sources.start);
+        let mut merged: Vec<Piece> = vec![];
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last) if last.get(last.sources.end - 1) + 1 == piece.dest_start => {
+                    last.sources.end = piece.sources.end;
+                }
+                _ => merged.push(piece),
+            }
+        }
+
+        Ok(PiecewiseMap {
+            from: from.to_string(),
+            to: to.to_string(),
+            pieces: merged,
+        })
     }
 
     fn lowest_location(&self, seeds: &[u64]) -> u64 {
-        let min_location = seeds
+        let (min_location, _) = self.lowest_seed(seeds);
+        min_location
+    }
+
+    /// The lowest location any of `seeds` reaches, and the seed that gets there.
+    fn lowest_seed(&self, seeds: &[u64]) -> (u64, u64) {
+        let path = self.path("seed", "location").unwrap();
+        seeds
             .par_iter()
-            .map(|seed| find_dest(*seed, &self.seed_to_soil))
-            .map(|soil| find_dest(soil, &self.soil_to_fertilizer))
-            .map(|fertilizer| find_dest(fertilizer, &self.fertilizer_to_water))
-            .map(|water| find_dest(water, &self.water_to_light))
-            .map(|light| find_dest(light, &self.light_to_temperature))
-            .map(|temperature| find_dest(temperature, &self.temperature_to_humidity))
-            .map(|humidity| find_dest(humidity, &self.humidity_to_location))
+            .map(|&seed| {
+                let location = path
+                    .iter()
+                    .fold(seed, |value, stage| find_dest(value, &stage.maps));
+                (location, seed)
+            })
             .min()
-            .unwrap();
+            .unwrap()
+    }
 
-        min_location
+    fn lowest_location_in_ranges(&self, seeds: Vec<Range<u64>>) -> u64 {
+        self.convert_ranges(seeds, "seed", "location")
+            .unwrap()
+            .iter()
+            .map(|range| range.start)
+            .min()
+            .unwrap()
     }
 }
 
 impl Map {
+    /// Parses `dest_start source_start range`.
     fn new(input: &str) -> Option<Self> {
-        if input.is_empty() {
+        let mut nums = input.split_whitespace().map(|num| num.parse().ok());
+        let dest_start = nums.next()??;
+        let source_start = nums.next()??;
+        let range = nums.next()??;
+        if nums.next().is_some() {
             return None;
         }
-        let mut nums = input.split(' ');
-        let dest_start = nums.next().unwrap().parse().unwrap();
-        let source_start = nums.next().unwrap().parse().unwrap();
-        let range = nums.next().unwrap().parse().unwrap();
         Some(Self {
             dest_start,
             source_start,
@@ -72,33 +383,57 @@ impl Map {
         })
     }
 
-    fn get_dest(&self, source: u64) -> u64 {
-        assert!(source - self.source_start <= self.range);
-        let dest_range = source - self.source_start;
+    /// How many values the entry maps, cut short so that neither its sources
+    /// nor its destinations reach `u64::MAX`. Every lookup goes by this, so
+    /// an entry that runs past the end still maps the values it can.
+    fn len(&self) -> u64 {
+        self.range
+            .min(u64::MAX - self.source_start)
+            .min(u64::MAX - self.dest_start)
+    }
 
-        assert!(self.dest_start + dest_range <= self.dest_start + self.range);
+    fn get_dest(&self, source: u64) -> u64 {
+        debug_assert!(self.source_in_range(source));
+        self.dest_start + (source - self.source_start)
+    }
 
-        self.dest_start + dest_range
+    fn get_source(&self, dest: u64) -> u64 {
+        self.source_start + (dest - self.dest_start)
     }
 
     fn source_in_range(&self, source: u64) -> bool {
-        (self.source_start..(self.source_start + self.range)).contains(&source)
+        self.sources().contains(&source)
+    }
+
+    fn sources(&self) -> Range<u64> {
+        self.source_start..self.source_start + self.len()
+    }
+
+    fn dests(&self) -> Range<u64> {
+        self.dest_start..self.dest_start + self.len()
+    }
+
+    fn overflows(&self) -> bool {
+        self.len() < self.range
     }
 }
 
-fn map_vec(lines: &mut Lines<'_>) -> Vec<Map> {
-    lines.next(); // Throw away label
+/// The entries under a header, up to the next blank line.
+fn map_vec(lines: &mut NumberedLines<'_>) -> Result<Vec<Map>, AlmanacError> {
     let mut maps = vec![];
 
-    for line in lines {
-        if let Some(map) = Map::new(line) {
-            maps.push(map);
-        } else {
+    for (idx, line) in lines {
+        if line.trim().is_empty() {
             break;
         }
+        let map = Map::new(line).ok_or_else(|| AlmanacError::BadEntry {
+            line: idx + 1,
+            entry: line.to_string(),
+        })?;
+        maps.push(map);
     }
 
-    maps
+    Ok(maps)
 }
 
 fn find_dest(source: u64, dests: &[Map]) -> u64 {
@@ -115,54 +450,589 @@ fn find_dest(source: u64, dests: &[Map]) -> u64 {
         .unwrap_or(source)
 }
 
-pub fn part1(input: &str) {
-    let mut lines = input.lines();
+/// Splits `sources` where it crosses the edge of a map, pairing each piece
+/// with where its first value ends up. Like [`find_dest`], the first map
+/// covering a value wins, and values no map covers stay as they are.
+fn split_by_maps(sources: Range<u64>, dests: &[Map]) -> Vec<(Range<u64>, u64)> {
+    if sources.is_empty() {
+        return vec![];
+    }
+    let mut sources = vec![sources];
+    let mut mapped = vec![];
+
+    for map in dests {
+        let map_range = map.sources();
+        let mut unmapped = vec![];
+
+        for source in sources {
+            let start = source.start.max(map_range.start);
+            let end = source.end.min(map_range.end);
+            if start >= end {
+                unmapped.push(source);
+                continue;
+            }
+            mapped.push((start..end, map.get_dest(start)));
+            if source.start < start {
+                unmapped.push(source.start..start);
+            }
+            if end < source.end {
+                unmapped.push(end..source.end);
+            }
+        }
+        sources = unmapped;
+    }
+
+    mapped.extend(sources.into_iter().map(|source| {
+        let start = source.start;
+        (source, start)
+    }));
+    mapped
+}
+
+/// Maps whole ranges of sources at once, splitting them where they cross the
+/// edge of a map.
+fn find_dest_ranges(sources: Vec<Range<u64>>, dests: &[Map]) -> Vec<Range<u64>> {
+    sources
+        .into_iter()
+        .flat_map(|source| split_by_maps(source, dests))
+        .map(|(source, dest_start)| dest_start..dest_start + (source.end - source.start))
+        .collect()
+}
+
+/// The seed numbers from the first line, and the maps after them.
+pub fn parse_almanac(input: &str) -> Result<(Vec<u64>, MapCollection), AlmanacError> {
+    let mut lines = input.lines().enumerate();
 
-    let seed_line = lines.next().unwrap();
+    let (_, seed_line) = lines.next().unwrap_or_default();
     let seeds: Vec<u64> = seed_line
-        .split(':')
-        .last()
+        .strip_prefix("seeds:")
+        .and_then(|seeds| {
+            seeds
+                .split_whitespace()
+                .map(|num| num.parse().ok())
+                .collect()
+        })
+        .ok_or_else(|| AlmanacError::BadSeeds(seed_line.to_string()))?;
+
+    let maps = MapCollection::new(&mut lines)?;
+
+    Ok((seeds, maps))
+}
+
+/// Removes every value covered by `covered` from `range`.
+fn subtract_ranges(range: Range<u64>, covered: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut remaining = vec![range];
+    for cut in covered {
+        remaining = remaining
+            .into_iter()
+            .flat_map(|range| {
+                if cut.end <= range.start || range.end <= cut.start {
+                    return vec![range];
+                }
+                [range.start..cut.start, cut.end..range.end]
+                    .into_iter()
+                    .filter(|piece| piece.start < piece.end)
+                    .collect()
+            })
+            .collect();
+    }
+    remaining
+}
+
+/// The inverse of [`find_dest_ranges`]: every source that one of `dests` is
+/// reached from. A source only counts for the first map covering it, and a
+/// source no map covers reaches itself.
+fn find_source_ranges(dests: Vec<Range<u64>>, maps: &[Map]) -> Vec<Range<u64>> {
+    let mut sources = vec![];
+    let mut covered = vec![];
+
+    for map in maps {
+        let map_dests = map.dests();
+        for dest in &dests {
+            let start = dest.start.max(map_dests.start);
+            let end = dest.end.min(map_dests.end);
+            if start < end {
+                let candidate = map.get_source(start)..map.get_source(end - 1) + 1;
+                sources.extend(subtract_ranges(candidate, &covered));
+            }
+        }
+        covered.push(map.sources());
+    }
+
+    for dest in dests {
+        sources.extend(subtract_ranges(dest, &covered));
+    }
+    sources
+}
+
+/// A run of consecutive sources that all move by the same amount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub sources: Range<u64>,
+    pub dest_start: u64,
+}
+
+impl Piece {
+    pub fn get(&self, source: u64) -> u64 {
+        self.dest_start + (source - self.sources.start)
+    }
+}
+
+/// One category mapped straight to another, as sorted pieces that between
+/// them cover every value below `u64::MAX`. No map covers `u64::MAX` itself,
+/// so it always maps to itself. Neighbouring pieces always move by different
+/// amounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiecewiseMap {
+    from: String,
+    to: String,
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
+
+    pub fn get(&self, source: u64) -> u64 {
+        let idx = self
+            .pieces
+            .partition_point(|piece| piece.sources.end <= source);
+        self.pieces
+            .get(idx)
+            .map_or(source, |piece| piece.get(source))
+    }
+
+    /// Tab separated, one piece per row after a header naming the
+    /// categories. Ends are exclusive.
+    pub fn to_table(&self) -> String {
+        let (from, to) = (&self.from, &self.to);
+        let mut table = format!("{from}_start\t{from}_end\t{to}_start\t{to}_end\n");
+        for piece in &self.pieces {
+            let dest_end = piece.get(piece.sources.end - 1) + 1;
+            table += &format!(
+                "{}\t{}\t{}\t{dest_end}\n",
+                piece.sources.start, piece.sources.end, piece.dest_start
+            );
+        }
+        table
+    }
+}
+
+/// The value of one seed in each category from `seed` through to `location`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedPath {
+    pub steps: Vec<(String, u64)>,
+}
+
+impl SeedPath {
+    pub fn seed(&self) -> u64 {
+        self.steps[0].1
+    }
+
+    pub fn location(&self) -> u64 {
+        self.steps[self.steps.len() - 1].1
+    }
+}
+
+/// The seed with the lowest location, and its path there.
+pub fn lowest_seed(input: &str) -> SeedPath {
+    let (seeds, maps) = parse_almanac(input).unwrap();
+
+    let (_, seed) = maps.lowest_seed(&seeds);
+    maps.trace(seed, "seed", "location").unwrap()
+}
+
+/// The seed within the ranges with the lowest location, and its path there.
+/// It is found by mapping that location back to every seed that reaches it.
+pub fn lowest_seed_in_ranges(input: &str) -> SeedPath {
+    let (seed_ranges, maps) = parse_seed_ranges(input).unwrap();
+
+    let min_location = maps.lowest_location_in_ranges(seed_ranges.clone());
+    let seed = maps
+        .invert(min_location, "seed", "location")
         .unwrap()
-        .trim()
-        .split(' ')
-        .map(|num| num.parse().unwrap())
-        .collect();
+        .iter()
+        .flat_map(|candidates| {
+            seed_ranges.iter().filter_map(|range| {
+                let start = range.start.max(candidates.start);
+                (start < range.end.min(candidates.end)).then_some(start)
+            })
+        })
+        .min()
+        .unwrap();
+    maps.trace(seed, "seed", "location").unwrap()
+}
 
-    let maps = MapCollection::new(&mut lines);
+pub fn part1(input: &str) -> u64 {
+    let (seeds, maps) = parse_almanac(input).unwrap();
 
     let min_location = maps.lowest_location(&seeds);
 
-    println!("{min_location}")
+    println!("{min_location}");
+    min_location
 }
 
-pub fn part2(input: &str) {
-    let mut lines = input.lines();
+/// The seed line read as `start length` pairs. Like map entries, ranges are
+/// cut short so that no seed reaches `u64::MAX`.
+fn parse_seed_ranges(input: &str) -> Result<(Vec<Range<u64>>, MapCollection), AlmanacError> {
+    let (seeds, maps) = parse_almanac(input)?;
 
-    let seed_ranges: Vec<u64> = lines
-        .next()
-        .unwrap()
-        .split(':')
-        .last()
-        .unwrap()
-        .trim()
-        .split(' ')
-        .map(|num| num.parse().unwrap())
+    if seeds.len() % 2 == 1 {
+        return Err(AlmanacError::UnpairedSeed(seeds[seeds.len() - 1]));
+    }
+    // Empty ranges hold no seeds, so they have no location to offer
+    let seed_ranges = seeds
+        .chunks(2)
+        .map(|pair| pair[0]..pair[0].saturating_add(pair[1]))
+        .filter(|range| !range.is_empty())
         .collect();
+    Ok((seed_ranges, maps))
+}
 
-    let maps = MapCollection::new(&mut lines);
+pub fn part2(input: &str) -> u64 {
+    let (seed_ranges, maps) = parse_seed_ranges(input).unwrap();
+
+    let min_location = maps.lowest_location_in_ranges(seed_ranges);
+
+    println!("{min_location}");
+    min_location
+}
+
+/// Part 2 by pushing every single seed through the maps. Far too slow for a
+/// real almanac, but useful to check [`part2`] against.
+pub fn part2_brute_force(input: &str) -> u64 {
+    let (seed_ranges, maps) = parse_seed_ranges(input).unwrap();
 
     let min_location = seed_ranges
-        .chunks(2)
-        .enumerate()
-        .map(|(idx, c)| {
-            let base = c[0];
-            let ceiling = base + c[1];
-            let seeds: Vec<u64> = (base..ceiling).collect::<Vec<_>>();
-            println!("Range {idx}, {} seeds", seeds.len());
-            maps.lowest_location(&seeds)
-        })
+        .into_iter()
+        .map(|range| maps.lowest_location(&range.collect::<Vec<_>>()))
         .min()
         .unwrap();
 
-    println!("{min_location}")
+    println!("{min_location}");
+    min_location
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        lowest_seed, lowest_seed_in_ranges, parse_almanac, parse_seed_ranges, part1, part2,
+        part2_brute_force, AlmanacError, MapIssue, Piece,
+    };
+
+    #[test]
+    fn test_part1() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part1(input), 35);
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = include_str!("../input_simple.txt");
+        assert_eq!(part2(input), 46);
+        assert_eq!(part2_brute_force(input), 46);
+    }
+
+    #[test]
+    fn test_part2_matches_brute_force() {
+        let maps = include_str!("../input_simple.txt")
+            .split_once('\n')
+            .unwrap()
+            .1;
+        let seed_lists = [
+            "seeds: 0 100",
+            "seeds: 97 5 10 3",
+            "seeds: 50 1 14 2 60 30",
+            "seeds: 79 14 0 0",
+            "seeds: 79 14 55 0",
+        ];
+        for seeds in seed_lists {
+            let input = format!("{seeds}\n{maps}");
+            assert_eq!(part2(&input), part2_brute_force(&input), "{seeds}");
+        }
+        assert_eq!(part2(&format!("seeds: 79 14 0 0\n{maps}")), 46);
+
+        // Cut short at `u64::MAX`, where no map reaches
+        let input = format!("seeds: {} 10\n{maps}", u64::MAX - 5);
+        assert_eq!(part2(&input), u64::MAX - 5);
+        assert_eq!(part2_brute_force(&input), u64::MAX - 5);
+
+        let input = format!("seeds: 79 14 55\n{maps}");
+        assert_eq!(
+            parse_seed_ranges(&input).err(),
+            Some(AlmanacError::UnpairedSeed(55))
+        );
+    }
+
+    #[test]
+    fn test_convert() {
+        let input = include_str!("../input_simple.txt");
+        let (_, maps) = parse_almanac(input).unwrap();
+
+        assert_eq!(maps.categories().count(), 8);
+        // Seed 79 has soil 81, fertilizer 81, water 81 and light 74
+        assert_eq!(maps.convert(79, "seed", "light"), Ok(74));
+        assert_eq!(maps.convert(81, "soil", "light"), Ok(74));
+        assert_eq!(maps.convert(81, "soil", "soil"), Ok(81));
+        assert_eq!(
+            maps.convert_ranges(vec![79..80, 14..15], "seed", "location"),
+            Ok(vec![82..83, 43..44])
+        );
+        assert_eq!(
+            maps.convert_ranges(vec![0..0, 5..5], "seed", "location"),
+            Ok(vec![])
+        );
+
+        assert_eq!(
+            maps.convert(1, "seed", "mineral"),
+            Err(AlmanacError::UnknownCategory("mineral".to_string()))
+        );
+        assert_eq!(
+            maps.convert(1, "light", "soil"),
+            Err(AlmanacError::Unreachable {
+                from: "light".to_string(),
+                to: "soil".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_stages_in_any_order() {
+        let input = "seeds: 1 5\n\n\
+                     b-to-c map:\n10 0 100\n\n\
+                     a-to-b map:\n0 1 1\n\n\
+                     b-to-d map:\n20 0 100\n";
+        let (seeds, maps) = parse_almanac(input).unwrap();
+
+        assert_eq!(seeds, vec![1, 5]);
+        assert_eq!(maps.convert(1, "a", "c"), Ok(10));
+        assert_eq!(maps.convert(5, "a", "d"), Ok(25));
+        assert_eq!(
+            maps.convert(1, "c", "d"),
+            Err(AlmanacError::Unreachable {
+                from: "c".to_string(),
+                to: "d".to_string()
+            })
+        );
+
+        assert_eq!(
+            parse_almanac("seeds: 1\n\nnot a header\n").err(),
+            Some(AlmanacError::BadHeader("not a header".to_string()))
+        );
+        assert_eq!(
+            parse_almanac("seeds: x").err(),
+            Some(AlmanacError::BadSeeds("seeds: x".to_string()))
+        );
+        assert_eq!(
+            parse_almanac("").err(),
+            Some(AlmanacError::BadSeeds(String::new()))
+        );
+        let err = parse_almanac("seeds: 1\n\na-to-b map:\n1 2 3\n1 2\n").err();
+        assert_eq!(
+            err,
+            Some(AlmanacError::BadEntry {
+                line: 5,
+                entry: "1 2".to_string()
+            })
+        );
+        assert_eq!(
+            err.unwrap().to_string(),
+            "line 5: `1 2` is not three numbers"
+        );
+    }
+
+    #[test]
+    fn test_lowest_seed() {
+        let input = include_str!("../input_simple.txt");
+        let steps = |values: [u64; 8]| -> Vec<(String, u64)> {
+            [
+                "seed",
+                "soil",
+                "fertilizer",
+                "water",
+                "light",
+                "temperature",
+                "humidity",
+                "location",
+            ]
+            .iter()
+            .map(|category| category.to_string())
+            .zip(values)
+            .collect()
+        };
+
+        let path = lowest_seed(input);
+        assert_eq!(path.seed(), 13);
+        assert_eq!(path.steps, steps([13, 13, 52, 41, 34, 34, 35, 35]));
+
+        let path = lowest_seed_in_ranges(input);
+        assert_eq!(path.seed(), 82);
+        assert_eq!(path.location(), 46);
+        assert_eq!(path.steps, steps([82, 84, 84, 84, 77, 45, 46, 46]));
+    }
+
+    #[test]
+    fn test_invert() {
+        let input = include_str!("../input_simple.txt");
+        let (_, maps) = parse_almanac(input).unwrap();
+
+        for location in 0..120 {
+            let seeds = maps.invert(location, "seed", "location").unwrap();
+            for seed in 0..200 {
+                let reaches = maps.convert(seed, "seed", "location") == Ok(location);
+                let found = seeds.iter().any(|range| range.contains(&seed));
+                assert_eq!(reaches, found, "seed {seed}, location {location}");
+            }
+        }
+
+        let lights = 74..76;
+        let soils = maps.invert_ranges(vec![lights], "soil", "light").unwrap();
+        assert!(soils.iter().any(|range| range.contains(&81)));
+        assert!(soils.iter().any(|range| range.contains(&82)));
+    }
+
+    #[test]
+    fn test_compose() {
+        let input = include_str!("../input_simple.txt");
+        let (_, maps) = parse_almanac(input).unwrap();
+        let composed = maps.compose("seed", "location").unwrap();
+
+        for seed in (0..200).chain([u64::MAX - 1, u64::MAX]) {
+            assert_eq!(
+                Ok(composed.get(seed)),
+                maps.convert(seed, "seed", "location"),
+                "seed {seed}"
+            );
+        }
+
+        let pieces = composed.pieces();
+        assert_eq!(pieces[0].sources.start, 0);
+        assert_eq!(pieces[pieces.len() - 1].sources.end, u64::MAX);
+        for pair in pieces.windows(2) {
+            assert_eq!(pair[0].sources.end, pair[1].sources.start);
+            assert_ne!(pair[0].get(pair[0].sources.end - 1) + 1, pair[1].dest_start);
+        }
+
+        let table = composed.to_table();
+        let mut rows = table.lines();
+        assert_eq!(
+            rows.next(),
+            Some("seed_start\tseed_end\tlocation_start\tlocation_end")
+        );
+        assert_eq!(rows.count(), pieces.len());
+
+        let (_, maps) = parse_almanac("seeds: 1\n\na-to-b map:\n10 0 5\n").unwrap();
+        assert_eq!(
+            maps.compose("a", "b").unwrap().pieces(),
+            [
+                Piece {
+                    sources: 0..5,
+                    dest_start: 10
+                },
+                Piece {
+                    sources: 5..u64::MAX,
+                    dest_start: 5
+                }
+            ]
+        );
+
+        // Entries running into `u64::MAX` from either side
+        let input = format!(
+            "seeds: 1\n\na-to-b map:\n0 {} 5\n{} 0 5\n\nb-to-c map:\n7 {} 3\n",
+            u64::MAX - 2,
+            u64::MAX - 1,
+            u64::MAX - 1
+        );
+        let (_, maps) = parse_almanac(&input).unwrap();
+        for (from, to) in [("a", "b"), ("a", "c"), ("b", "c")] {
+            let composed = maps.compose(from, to).unwrap();
+            let edges = [0, 1, 3, 5, 7, 10, u64::MAX - 3, u64::MAX - 2, u64::MAX - 1];
+            for value in edges.into_iter().chain([u64::MAX]) {
+                assert_eq!(
+                    Ok(composed.get(value)),
+                    maps.convert(value, from, to),
+                    "{from} to {to}, {value}"
+                );
+            }
+        }
+        assert_eq!(maps.convert(u64::MAX - 2, "a", "b"), Ok(0));
+        assert_eq!(maps.convert(u64::MAX, "a", "b"), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_validate() {
+        let input = include_str!("../input_simple.txt");
+        let (_, maps) = parse_almanac(input).unwrap();
+        assert!(maps.validate().is_empty());
+
+        let input = format!(
+            "seeds: 1\n\n\
+             a-to-b map:\n0 10 5\n50 12 5\n70 10 5\n\n\
+             b-to-c map:\n1 {} 5\n",
+            u64::MAX - 2
+        );
+        let (_, maps) = parse_almanac(&input).unwrap();
+        let issues = maps.validate();
+        assert_eq!(
+            issues,
+            [
+                MapIssue::Overlap {
+                    stage: "a-to-b".to_string(),
+                    first: 1,
+                    second: 2,
+                    sources: 12..15
+                },
+                MapIssue::Duplicate {
+                    stage: "a-to-b".to_string(),
+                    first: 1,
+                    second: 3
+                },
+                MapIssue::Overlap {
+                    stage: "a-to-b".to_string(),
+                    first: 2,
+                    second: 3,
+                    sources: 12..15
+                },
+                MapIssue::Overflow {
+                    stage: "b-to-c".to_string(),
+                    entry: 1
+                },
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "a-to-b: entries 1 and 2 both cover 12..15"
+        );
+
+        // The first entry still wins, and the overflowing one stops at the end
+        assert_eq!(maps.convert(13, "a", "b"), Ok(3));
+        assert_eq!(maps.compose("a", "b").unwrap().get(16), 54);
+        assert_eq!(maps.convert(u64::MAX - 1, "b", "c"), Ok(2));
+        assert_eq!(maps.convert(9, "b", "c"), Ok(9));
+
+        // Destinations running past the end are cut short too
+        let input = format!("seeds: 1\n\na-to-b map:\n{} 0 5\n", u64::MAX - 1);
+        let (_, maps) = parse_almanac(&input).unwrap();
+        assert_eq!(
+            maps.validate(),
+            [MapIssue::Overflow {
+                stage: "a-to-b".to_string(),
+                entry: 1
+            }]
+        );
+        assert_eq!(
+            maps.validate()[0].to_string(),
+            format!("a-to-b: entry 1 reaches {}, so it is cut short", u64::MAX)
+        );
+        assert_eq!(maps.convert(0, "a", "b"), Ok(u64::MAX - 1));
+        assert_eq!(maps.convert(3, "a", "b"), Ok(3));
+        assert_eq!(maps.compose("a", "b").unwrap().get(3), 3);
+        let unmapped = 3..4;
+        assert_eq!(maps.invert(3, "a", "b"), Ok(vec![unmapped]));
+        assert_eq!(
+            maps.invert(u64::MAX - 1, "a", "b"),
+            Ok(vec![0..1, u64::MAX - 1..u64::MAX])
+        );
+    }
 }